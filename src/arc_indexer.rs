@@ -0,0 +1,298 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Formatter};
+use core::hash::{BuildHasher, Hash};
+use core::ops::Index;
+
+use hashbrown::Equivalent;
+
+use crate::{DefaultHashBuilder, DupIndexerRefs, IndexType};
+
+/// An interner that stores each unique value once as an `Arc<T>`, e.g. `Arc<str>` or `Arc<[u8]>`,
+/// and returns a cloned `Arc` handle together with the index of each inserted value,
+/// so that the value can be kept and shared between threads without borrowing the indexer.
+///
+/// It is a thin wrapper around `DupIndexerRefs<Arc<T>>`, so values can be inserted and looked up
+/// by reference, e.g. by `&str` for `ArcDupIndexer<str>`, and are only allocated if they are new.
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use dup_indexer::ArcDupIndexer;
+/// # fn main() {
+/// let mut di = ArcDupIndexer::<str>::new();
+/// let (index, hello) = di.insert_ref("hello");
+/// assert_eq!(index, 0);
+/// assert_eq!(&*hello, "hello");
+/// let (index, hello2) = di.insert_ref("hello");
+/// assert_eq!(index, 0);
+/// assert!(Arc::ptr_eq(&hello, &hello2));
+/// assert_eq!(di.get_index_of("hello"), Some(0));
+/// # }
+/// ```
+pub struct ArcDupIndexer<T: ?Sized, S = DefaultHashBuilder, I = usize>
+where
+    T: Eq + Hash,
+{
+    indexer: DupIndexerRefs<Arc<T>, S, I>,
+}
+
+impl<T> ArcDupIndexer<T>
+where
+    T: Eq + Hash + ?Sized,
+    for<'a> Arc<T>: From<&'a T>,
+{
+    /// Create a new instance of `ArcDupIndexer<T>`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Constructs a new, empty `ArcDupIndexer<T>` with at least the specified capacity.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<T, I> Default for ArcDupIndexer<T, DefaultHashBuilder, I>
+where
+    T: Eq + Hash + ?Sized,
+    for<'a> Arc<T>: From<&'a T>,
+    I: IndexType,
+{
+    fn default() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+}
+
+impl<T, S, I> ArcDupIndexer<T, S, I>
+where
+    T: Eq + Hash + ?Sized,
+    for<'a> Arc<T>: From<&'a T>,
+    S: BuildHasher,
+    I: IndexType,
+{
+    /// Create a new instance of `ArcDupIndexer<T>` using the provided hasher.
+    #[must_use]
+    pub fn with_hasher(hasher: S) -> Self {
+        Self {
+            indexer: DupIndexerRefs::with_hasher(hasher),
+        }
+    }
+
+    /// Constructs a new, empty `ArcDupIndexer<T>` with at least the specified capacity
+    /// using the provided hasher.
+    #[must_use]
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        Self {
+            indexer: DupIndexerRefs::with_capacity_and_hasher(capacity, hasher),
+        }
+    }
+
+    /// Get the number of values in the indexer.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.indexer.len()
+    }
+
+    /// Return true if the indexer is empty.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.indexer.is_empty()
+    }
+
+    /// Returns the handle of the value at the given index, or `None` if it is out of bounds.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, index: I) -> Option<&Arc<T>> {
+        self.indexer.get(index)
+    }
+
+    /// Insert a value into the indexer if it doesn't already exist, and return the index
+    /// of the value together with a handle to it. The value is only copied into a new `Arc`
+    /// if it does not exist yet.
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert_ref(&mut self, value: &T) -> (I, Arc<T>) {
+        let index = self.indexer.insert_ref(value);
+        (index, Arc::clone(&self.indexer[index]))
+    }
+
+    /// Insert a shared value into the indexer if it doesn't already exist, and return the index
+    /// of the value together with a handle to it. If an equal value already exists,
+    /// the returned handle points to the existing value rather than to the given one.
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert(&mut self, value: Arc<T>) -> (I, Arc<T>) {
+        let index = self.indexer.insert_owned(value);
+        (index, Arc::clone(&self.indexer[index]))
+    }
+
+    /// Return the index of the value if it exists in the indexer, without inserting it.
+    /// The value may be `&T`, e.g. `&str` for `ArcDupIndexer<str>`,
+    /// or any other type that is [`Equivalent`] to it.
+    #[must_use]
+    pub fn get_index_of<Q>(&self, value: &Q) -> Option<I>
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+    {
+        self.indexer.get_index_of(value)
+    }
+
+    /// Return true if the value exists in the indexer.
+    #[inline]
+    #[must_use]
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+    {
+        self.indexer.contains(value)
+    }
+
+    /// Remove all values that are no longer referenced by any handle outside of the indexer,
+    /// and compact the remaining values, preserving their order. Returns a table that maps
+    /// each old index to the new index of the value, or to `None` if the value was freed.
+    ///
+    /// This allows a long-lived symbol table to reclaim the values that are no longer in use,
+    /// as long as the consumers hold on to the returned handles rather than just to the indexes.
+    /// Any indexes held elsewhere must be updated using the returned table.
+    ///
+    /// ```
+    /// # use dup_indexer::ArcDupIndexer;
+    /// # fn main() {
+    /// let mut di = ArcDupIndexer::<str>::new();
+    /// let (_, foo) = di.insert_ref("foo");
+    /// let (_, bar) = di.insert_ref("bar");
+    /// di.insert_ref("baz");
+    /// drop(foo);
+    /// assert_eq!(di.collect_garbage(), vec![None, Some(0), None]);
+    /// assert_eq!(di.get_index_of("bar"), Some(0));
+    /// assert_eq!(di.get_index_of("foo"), None);
+    /// # }
+    /// ```
+    pub fn collect_garbage(&mut self) -> Vec<Option<I>> {
+        self.indexer.retain(|value| Arc::strong_count(value) > 1)
+    }
+
+    /// Returns a reference to the underlying [`DupIndexerRefs`].
+    #[inline]
+    #[must_use]
+    pub fn as_indexer(&self) -> &DupIndexerRefs<Arc<T>, S, I> {
+        &self.indexer
+    }
+
+    /// Converts the interner into the underlying [`DupIndexerRefs`].
+    #[inline]
+    #[must_use]
+    pub fn into_indexer(self) -> DupIndexerRefs<Arc<T>, S, I> {
+        self.indexer
+    }
+
+    /// Converts the interner into a vector of handles, where each value is stored at its index.
+    #[inline]
+    #[must_use]
+    pub fn into_vec(self) -> Vec<Arc<T>> {
+        self.indexer.into_vec()
+    }
+}
+
+impl<T, S, I> Index<I> for ArcDupIndexer<T, S, I>
+where
+    T: Eq + Hash + ?Sized,
+    I: IndexType,
+{
+    type Output = Arc<T>;
+
+    #[inline]
+    fn index(&self, index: I) -> &Self::Output {
+        &self.indexer[index]
+    }
+}
+
+impl<T, S, I> Debug for ArcDupIndexer<T, S, I>
+where
+    T: Eq + Hash + Debug + ?Sized,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.indexer.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::RandomState;
+
+    use super::*;
+
+    #[test]
+    fn test_arc_str() {
+        let mut di: ArcDupIndexer<str> = ArcDupIndexer::with_capacity(2);
+        assert!(di.is_empty());
+        let (foo_index, foo) = di.insert_ref("foo");
+        assert_eq!(foo_index, 0);
+        assert_eq!(di.insert(Arc::from("bar")).0, 1);
+        let (index, foo2) = di.insert(Arc::from("foo"));
+        assert_eq!(index, 0);
+        assert!(Arc::ptr_eq(&foo, &foo2));
+        // The interner, `foo` and `foo2` share the same allocation
+        assert_eq!(Arc::strong_count(&foo), 3);
+        assert_eq!(di.get_index_of("bar"), Some(1));
+        assert!(!di.contains("baz"));
+        assert_eq!(di.get(1).map(|v| &**v), Some("bar"));
+        assert_eq!(&*di[0], "foo");
+        assert_eq!(format!("{di:?}"), r#"{0: "foo", 1: "bar"}"#);
+        drop(di);
+        assert_eq!(&*foo, "foo");
+        assert_eq!(Arc::strong_count(&foo), 2);
+    }
+
+    #[test]
+    fn test_collect_garbage() {
+        let mut di: ArcDupIndexer<str, RandomState, u8> =
+            ArcDupIndexer::with_hasher(RandomState::new());
+        let handles: Vec<Arc<str>> = (0..10).map(|v| di.insert_ref(&v.to_string()).1).collect();
+        assert!(di.collect_garbage().iter().all(Option::is_some));
+        let kept: Vec<Arc<str>> = handles.into_iter().skip(7).collect();
+        let weak = Arc::downgrade(&di[0]);
+        let remap = di.collect_garbage();
+        assert_eq!(remap.iter().filter(|v| v.is_none()).count(), 7);
+        assert_eq!(remap[7..], [Some(0), Some(1), Some(2)]);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(di.len(), 3);
+        assert_eq!(di.get_index_of("8"), Some(1));
+        assert!(Arc::ptr_eq(&di.insert_ref("9").1, &kept[2]));
+        assert_eq!(di.insert_ref("0").0, 3);
+        drop(kept);
+        assert_eq!(di.collect_garbage(), vec![None; 4]);
+        assert!(di.is_empty());
+    }
+
+    #[test]
+    fn test_arc_bytes_across_threads() {
+        let mut di: ArcDupIndexer<[u8], RandomState, u16> =
+            ArcDupIndexer::with_hasher(RandomState::new());
+        let handles: Vec<(u16, Arc<[u8]>)> = [b"ab".as_slice(), b"cd", b"ab"]
+            .map(|v| di.insert_ref(v))
+            .to_vec();
+        let total = std::thread::scope(|s| {
+            let threads: Vec<_> = handles
+                .iter()
+                .map(|(_, value)| s.spawn(move || value.len()))
+                .collect();
+            threads
+                .into_iter()
+                .map(|t| t.join().unwrap())
+                .sum::<usize>()
+        });
+        assert_eq!(total, 6);
+        assert_eq!(handles[0].0, handles[2].0);
+        assert_eq!(di.len(), 2);
+        assert_eq!(di.into_indexer().len(), 2);
+    }
+}
@@ -0,0 +1,351 @@
+use core::borrow::Borrow;
+use core::fmt::{Debug, Formatter};
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use hashbrown::hash_table::Entry::{Occupied, Vacant};
+use hashbrown::{Equivalent, HashTable};
+
+use crate::index::next_index;
+use crate::{make_hash, DefaultHashBuilder, DupIndexer, IndexType};
+
+/// A shard of the concurrent indexer, containing the values whose hashes map to it.
+struct Shard<T> {
+    /// The values of this shard, together with their global indexes.
+    entries: Vec<(usize, T)>,
+    /// Positions in `entries`, hashed and compared using the values they point to.
+    lookup: HashTable<usize>,
+}
+
+/// An indexer that can be shared between threads, and inserted into via `&self`.
+///
+/// The values are split into shards by their hash, each protected by its own lock,
+/// so that threads inserting different values rarely wait for each other.
+/// Each unique value still gets a unique index from a single dense sequence,
+/// but the order of indexes depends on the order in which the threads insert the values.
+/// When done, convert it into a vector with [`ConcurrentDupIndexer::into_vec`],
+/// or into a regular [`DupIndexer`] with [`ConcurrentDupIndexer::into_indexer`].
+///
+/// ```
+/// # use dup_indexer::ConcurrentDupIndexer;
+/// # fn main() {
+/// let di = ConcurrentDupIndexer::<String>::new();
+/// std::thread::scope(|s| {
+///     s.spawn(|| di.insert_ref("hello"));
+///     s.spawn(|| di.insert("world".to_string()));
+///     s.spawn(|| di.insert_ref("hello"));
+/// });
+/// assert_eq!(di.len(), 2);
+/// let index = di.insert_ref("world");
+/// assert_eq!(di.into_vec()[index], "world");
+/// # }
+/// ```
+pub struct ConcurrentDupIndexer<T, S = DefaultHashBuilder, I = usize> {
+    shards: Box<[Mutex<Shard<T>>]>,
+    len: AtomicUsize,
+    hasher: S,
+    _index: PhantomData<fn() -> I>,
+}
+
+impl<T> ConcurrentDupIndexer<T> {
+    /// Create a new instance of `ConcurrentDupIndexer<T>`, with the number of shards
+    /// based on the available parallelism.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Create a new instance of `ConcurrentDupIndexer<T>` with the given number of shards.
+    #[must_use]
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, DefaultHashBuilder::default())
+    }
+}
+
+impl<T> Default for ConcurrentDupIndexer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, S: BuildHasher, I: IndexType> ConcurrentDupIndexer<T, S, I> {
+    /// Create a new instance of `ConcurrentDupIndexer<T>` using the provided hasher,
+    /// with the number of shards based on the available parallelism.
+    #[must_use]
+    pub fn with_hasher(hasher: S) -> Self {
+        let parallelism = std::thread::available_parallelism().map_or(4, usize::from);
+        Self::with_shards_and_hasher(parallelism * 4, hasher)
+    }
+
+    /// Create a new instance of `ConcurrentDupIndexer<T>` with the given number of shards
+    /// using the provided hasher.
+    #[must_use]
+    pub fn with_shards_and_hasher(shards: usize, hasher: S) -> Self {
+        let shards = (0..shards.max(1))
+            .map(|_| {
+                Mutex::new(Shard {
+                    entries: Vec::new(),
+                    lookup: HashTable::new(),
+                })
+            })
+            .collect();
+        Self {
+            shards,
+            len: AtomicUsize::new(0),
+            hasher,
+            _index: PhantomData,
+        }
+    }
+
+    /// Get the number of values in the indexer.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Return true if the indexer is empty.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Converts the indexer into a vector, where each value is stored at its index.
+    ///
+    /// # Panics
+    /// Panics if an index was given out without storing its value, which would shift all later values.
+    #[must_use]
+    pub fn into_vec(self) -> Vec<T> {
+        self.into_parts().0
+    }
+
+    fn into_parts(self) -> (Vec<T>, S) {
+        let mut values: Vec<Option<T>> = (0..self.len.into_inner()).map(|_| None).collect();
+        for shard in self.shards.into_vec() {
+            let shard = shard.into_inner().unwrap_or_else(PoisonError::into_inner);
+            for (index, value) in shard.entries {
+                values[index] = Some(value);
+            }
+        }
+        let values = values
+            .into_iter()
+            .map(|value| value.expect("a value is missing, an insertion must have panicked"))
+            .collect();
+        (values, self.hasher)
+    }
+
+    fn lock_shard(&self, hash: u64) -> MutexGuard<'_, Shard<T>> {
+        // The upper bits of the hash are used by the hash table, so use the middle ones
+        #[allow(clippy::cast_possible_truncation)]
+        let shard = (hash >> 32) as usize % self.shards.len();
+        self.shards[shard]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher, I: IndexType> ConcurrentDupIndexer<T, S, I> {
+    /// Insert a value into the indexer if it doesn't already exist,
+    /// and return the index of the value.
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the capacity of the index type `I`.
+    pub fn insert(&self, value: T) -> I {
+        let hash = make_hash(&self.hasher, &value);
+        self.insert_with::<T, T>(hash, value, |value| value)
+    }
+
+    /// Insert a value into the indexer if it doesn't already exist, and return the index of the value.
+    /// The value is only cloned if it does not already exist in the indexer.
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the capacity of the index type `I`.
+    pub fn insert_ref<Q>(&self, value: &Q) -> I
+    where
+        Q: Hash + Equivalent<T> + ToOwned<Owned = T> + ?Sized,
+    {
+        let hash = make_hash(&self.hasher, value);
+        self.insert_with::<Q, &Q>(hash, value, ToOwned::to_owned)
+    }
+
+    /// Return the index of the value if it exists in the indexer, without inserting it.
+    /// The value may be any [`Equivalent`] type, same as for [`DupIndexer::get_index_of`].
+    #[must_use]
+    pub fn get_index_of<Q>(&self, value: &Q) -> Option<I>
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+    {
+        let hash = make_hash(&self.hasher, value);
+        let shard = self.lock_shard(hash);
+        let entries = &shard.entries;
+        shard
+            .lookup
+            .find(hash, |&pos| value.equivalent(&entries[pos].1))
+            .and_then(|&pos| I::try_from_usize(entries[pos].0))
+    }
+
+    /// Return true if the value exists in the indexer.
+    /// The value may be any [`Equivalent`] type, see [`ConcurrentDupIndexer::get_index_of`].
+    #[inline]
+    #[must_use]
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+    {
+        self.get_index_of(value).is_some()
+    }
+
+    /// Converts the indexer into a regular [`DupIndexer`] with the same indexes of all values.
+    ///
+    /// # Panics
+    /// Panics if an index was given out without storing its value, see [`ConcurrentDupIndexer::into_vec`].
+    #[must_use]
+    pub fn into_indexer(self) -> DupIndexer<T, S, I> {
+        let (values, hasher) = self.into_parts();
+        let mut di = DupIndexer::with_capacity_and_hasher(values.len(), hasher);
+        for value in values {
+            di.insert(value);
+        }
+        di
+    }
+
+    /// Find the value in the shard for the given hash, or insert the value converted by `into_value`.
+    fn insert_with<Q, V>(&self, hash: u64, value: V, into_value: impl FnOnce(V) -> T) -> I
+    where
+        V: Borrow<Q>,
+        Q: Equivalent<T> + ?Sized,
+    {
+        let mut guard = self.lock_shard(hash);
+        let shard = &mut *guard;
+        let entries = &shard.entries;
+        let hasher = &self.hasher;
+        match shard.lookup.entry(
+            hash,
+            |&pos| value.borrow().equivalent(&entries[pos].1),
+            |&pos| make_hash(hasher, &entries[pos].1),
+        ) {
+            Occupied(entry) => next_index(shard.entries[*entry.get()].0),
+            Vacant(entry) => {
+                let value = into_value(value);
+                // Nothing may panic between taking the index and storing the value,
+                // otherwise that index would be left without a value.
+                shard.entries.reserve(1);
+                let index = self.len.fetch_add(1, Ordering::Relaxed);
+                let Some(typed_index) = I::try_from_usize(index) else {
+                    self.len.fetch_sub(1, Ordering::Relaxed);
+                    panic!("the number of values exceeds the capacity of the index type");
+                };
+                entry.insert(shard.entries.len());
+                shard.entries.push((index, value));
+                typed_index
+            }
+        }
+    }
+}
+
+impl<T, S, I> Debug for ConcurrentDupIndexer<T, S, I> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ConcurrentDupIndexer")
+            .field("len", &self.len.load(Ordering::Relaxed))
+            .field("shards", &self.shards.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::RandomState;
+
+    use super::*;
+
+    #[test]
+    fn test_single_thread() {
+        let di: ConcurrentDupIndexer<String> = ConcurrentDupIndexer::with_shards(3);
+        assert!(di.is_empty());
+        assert_eq!(di.insert("foo".to_string()), 0);
+        assert_eq!(di.insert_ref("bar"), 1);
+        assert_eq!(di.insert_ref("foo"), 0);
+        assert_eq!(di.insert("bar".to_string()), 1);
+        assert_eq!(di.get_index_of("bar"), Some(1));
+        assert!(di.contains("foo"));
+        assert!(!di.contains("baz"));
+        assert_eq!(di.len(), 2);
+        assert_eq!(
+            format!("{di:?}"),
+            "ConcurrentDupIndexer { len: 2, shards: 3, .. }"
+        );
+        assert_eq!(di.into_vec(), vec!["foo", "bar"]);
+    }
+
+    #[test]
+    fn test_panicking_hash() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        use std::sync::atomic::AtomicBool;
+
+        static PANIC: AtomicBool = AtomicBool::new(false);
+
+        // Panics while the shard rehashes its existing values to grow
+        #[derive(Debug, PartialEq, Eq)]
+        struct Value(u32);
+
+        impl Hash for Value {
+            fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+                assert!(
+                    self.0 != 0 || !PANIC.load(Ordering::Relaxed),
+                    "hash panicked"
+                );
+                self.0.hash(state);
+            }
+        }
+
+        let di: ConcurrentDupIndexer<Value> = ConcurrentDupIndexer::with_shards(1);
+        for value in 0..3 {
+            di.insert(Value(value));
+        }
+        PANIC.store(true, Ordering::Relaxed);
+        assert!(catch_unwind(AssertUnwindSafe(|| di.insert(Value(3)))).is_err());
+        PANIC.store(false, Ordering::Relaxed);
+        assert_eq!(di.insert(Value(4)), 3);
+        assert_eq!(di.into_vec(), vec![Value(0), Value(1), Value(2), Value(4)]);
+    }
+
+    #[test]
+    fn test_threads() {
+        const THREADS: usize = 4;
+        const VALUES: usize = 50;
+        let di: ConcurrentDupIndexer<String, RandomState, u32> =
+            ConcurrentDupIndexer::with_shards_and_hasher(8, RandomState::new());
+        let results: Vec<Vec<u32>> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|thread| {
+                    let di = &di;
+                    s.spawn(move || {
+                        (0..VALUES)
+                            .map(|value| {
+                                if thread % 2 == 0 {
+                                    di.insert(value.to_string())
+                                } else {
+                                    di.insert_ref(value.to_string().as_str())
+                                }
+                            })
+                            .collect()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(di.len(), VALUES);
+        for indexes in &results[1..] {
+            assert_eq!(indexes, &results[0]);
+        }
+        let di = di.into_indexer();
+        assert_eq!(di.len(), VALUES);
+        for (value, &index) in results[0].iter().enumerate() {
+            assert_eq!(di[index], value.to_string());
+            assert_eq!(di.get_index_of(value.to_string().as_str()), Some(index));
+        }
+    }
+}
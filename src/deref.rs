@@ -1,10 +1,28 @@
-use std::collections::hash_map::Entry::{Occupied, Vacant};
-use std::collections::HashMap;
-use std::fmt::{Debug, Formatter};
-use std::hash::{BuildHasher, Hash};
-use std::ops::{Deref, Index};
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::ffi::CString;
+use alloc::rc::Rc;
+use alloc::string::String;
+#[cfg(target_has_atomic = "ptr")]
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ffi::CStr;
+use core::fmt::{Debug, Formatter};
+use core::hash::{BuildHasher, Hash};
+use core::ops::{Deref, Index};
+#[cfg(feature = "std")]
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+};
 
-use crate::DefaultHashBuilder;
+use hashbrown::{Equivalent, HashTable};
+
+use crate::index::{compact_remap, sort_by_frequency};
+use crate::{
+    make_hash, CapacityError, DefaultHashBuilder, Entry, FrozenIndexer, IndexType, TryReserveError,
+};
 
 /// A value that can be stably dereferenced with [`Deref`] trait.
 /// A stable dereference means that a reference to the value will be valid
@@ -15,64 +33,149 @@ use crate::DefaultHashBuilder;
 /// # Safety
 /// Implementing this trait is unsafe because the implementation must guarantee that
 /// the [`Deref`] is stable per above.
+#[deprecated(
+    note = "DupIndexerRefs only stores indexes, implement `FromTarget` for custom key types instead"
+)]
 pub unsafe trait StableDerefKey: Deref + Eq + Hash {}
 
+#[allow(deprecated)]
 unsafe impl StableDerefKey for String {}
 
-pub struct DupIndexerRefs<T: StableDerefKey, S = DefaultHashBuilder>
+/// A value of [`DupIndexerRefs`] that can be created from a reference to its [`Deref::Target`],
+/// e.g. a `String` from a `&str`, so that the value is only created if it is not yet in the indexer.
+///
+/// It is implemented for `String`, `Box<str>`, `Rc<str>`, `Arc<str>`, `Vec<T>`, `Box<[T]>`,
+/// `PathBuf`, `OsString`, and `CString`, and for any type implementing the deprecated
+/// [`StableDerefKey`] whose target converts back to it with [`ToOwned`].
+pub trait FromTarget: Deref {
+    /// Create the value from a reference to its target.
+    fn from_target(value: &Self::Target) -> Self;
+}
+
+#[allow(deprecated)]
+impl<T> FromTarget for T
 where
-    <T as Deref>::Target: 'static,
+    T: StableDerefKey,
+    T::Target: ToOwned<Owned = T>,
 {
+    fn from_target(value: &Self::Target) -> Self {
+        value.to_owned()
+    }
+}
+
+impl FromTarget for Box<str> {
+    fn from_target(value: &str) -> Self {
+        value.into()
+    }
+}
+
+impl<T: Clone> FromTarget for Vec<T> {
+    fn from_target(value: &[T]) -> Self {
+        value.to_vec()
+    }
+}
+
+impl<T: Clone> FromTarget for Box<[T]> {
+    fn from_target(value: &[T]) -> Self {
+        value.into()
+    }
+}
+
+impl FromTarget for CString {
+    fn from_target(value: &CStr) -> Self {
+        value.to_owned()
+    }
+}
+
+impl<T: ?Sized> FromTarget for Rc<T>
+where
+    for<'a> Rc<T>: From<&'a T>,
+{
+    fn from_target(value: &T) -> Self {
+        value.into()
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl<T: ?Sized> FromTarget for Arc<T>
+where
+    for<'a> Arc<T>: From<&'a T>,
+{
+    fn from_target(value: &T) -> Self {
+        value.into()
+    }
+}
+
+#[cfg(feature = "std")]
+impl FromTarget for PathBuf {
+    fn from_target(value: &Path) -> Self {
+        value.to_owned()
+    }
+}
+
+#[cfg(feature = "std")]
+impl FromTarget for OsString {
+    fn from_target(value: &OsStr) -> Self {
+        value.to_owned()
+    }
+}
+
+pub struct DupIndexerRefs<T, S = DefaultHashBuilder, I = usize> {
     values: Vec<T>,
-    lookup: HashMap<&'static T::Target, usize, S>,
+    /// Indexes into `values`, hashed and compared using the dereferenced values they point to.
+    lookup: HashTable<I>,
+    hasher: S,
+    /// The number of insertions of each value, if enabled with [`DupIndexerRefs::enable_counts`].
+    counts: Option<Vec<usize>>,
+    /// The maximum number of values, set with [`DupIndexerRefs::set_max_len`].
+    max_len: usize,
 }
 
-impl<T> Default for DupIndexerRefs<T>
+impl<T, I> Default for DupIndexerRefs<T, DefaultHashBuilder, I>
 where
-    T: StableDerefKey,
-    T::Target: Eq + Hash + ToOwned<Owned = T>,
+    T: FromTarget + Eq + Hash,
+    T::Target: Eq + Hash,
+    I: IndexType,
 {
     fn default() -> Self {
-        Self::new()
+        Self::with_hasher(DefaultHashBuilder::default())
     }
 }
 
 impl<T> DupIndexerRefs<T>
 where
-    T: StableDerefKey,
-    T::Target: Eq + Hash + ToOwned<Owned = T>,
+    T: FromTarget + Eq + Hash,
+    T::Target: Eq + Hash,
 {
     /// Constructs a new, empty `DupGenIndexer`
     #[must_use]
     pub fn new() -> Self {
-        Self {
-            values: Vec::new(),
-            lookup: HashMap::with_hasher(DefaultHashBuilder::default()),
-        }
+        Self::with_hasher(DefaultHashBuilder::default())
     }
 
     /// Constructs a new, empty `DupGenIndexer` with at least the specified capacity.
     #[must_use]
     pub fn with_capacity(capacity: usize) -> Self {
-        Self {
-            values: Vec::with_capacity(capacity),
-            lookup: HashMap::with_capacity_and_hasher(capacity, DefaultHashBuilder::default()),
-        }
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
     }
 }
 
-impl<T, S> DupIndexerRefs<T, S>
+impl<T, S, I> DupIndexerRefs<T, S, I>
 where
-    T: StableDerefKey,
-    T::Target: Eq + Hash + ToOwned<Owned = T>,
+    T: FromTarget + Eq + Hash,
+    T::Target: Eq + Hash,
     S: BuildHasher,
+    I: IndexType,
 {
     /// Create a new instance of `DupIndexerRefs<T>` using the provided hasher.
     #[must_use]
     pub fn with_hasher(hasher: S) -> Self {
         Self {
             values: Vec::new(),
-            lookup: HashMap::with_hasher(hasher),
+            lookup: HashTable::new(),
+            hasher,
+            counts: None,
+            max_len: usize::MAX,
         }
     }
 
@@ -82,7 +185,10 @@ where
     pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
         Self {
             values: Vec::with_capacity(capacity),
-            lookup: HashMap::with_capacity_and_hasher(capacity, hasher),
+            lookup: HashTable::with_capacity(capacity),
+            hasher,
+            counts: None,
+            max_len: usize::MAX,
         }
     }
 
@@ -93,6 +199,20 @@ where
         self.values.capacity()
     }
 
+    /// Returns the maximum number of values the indexer may hold, `usize::MAX` by default.
+    #[inline]
+    #[must_use]
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Set the maximum number of values the indexer may hold, e.g. to fit the limits of an output format.
+    /// Inserting any new value beyond this limit panics, while [`DupIndexerRefs::try_insert_owned`]
+    /// and [`DupIndexerRefs::try_insert_ref`] return an error with the rejected value. The values that are already in the indexer are not removed.
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+    }
+
     /// Extracts a slice containing the entire indexer values.
     #[inline]
     #[must_use]
@@ -114,6 +234,242 @@ where
         self.values.is_empty()
     }
 
+    /// Returns a reference to the value at the given index, or `None` if it is out of bounds.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, index: I) -> Option<&T> {
+        self.values.get(index.index())
+    }
+
+    /// Retain only the values for which the predicate returns `true`, preserving their order.
+    /// Returns a table that maps each old index to the new index of the value,
+    /// or to `None` if the value was removed.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexerRefs;
+    /// # fn main() {
+    /// let mut di = DupIndexerRefs::<String>::new();
+    /// di.insert_ref("foo");
+    /// di.insert_ref("bar");
+    /// di.insert_ref("baz");
+    /// assert_eq!(di.retain(|v| v.starts_with('b')), vec![None, Some(0), Some(1)]);
+    /// assert_eq!(di.insert_ref("baz"), 1);
+    /// assert_eq!(di.into_vec(), vec!["bar", "baz"]);
+    /// # }
+    /// ```
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut keep: F) -> Vec<Option<I>> {
+        let remap: Vec<Option<I>> = compact_remap(self.values.iter().map(&mut keep));
+        self.lookup.retain(|index| match remap[index.index()] {
+            Some(new_index) => {
+                *index = new_index;
+                true
+            }
+            None => false,
+        });
+        let mut old_index = 0;
+        self.values.retain(|_| {
+            old_index += 1;
+            remap[old_index - 1].is_some()
+        });
+        if let Some(counts) = &mut self.counts {
+            let mut old_index = 0;
+            counts.retain(|_| {
+                old_index += 1;
+                remap[old_index - 1].is_some()
+            });
+        }
+        remap
+    }
+
+    /// Remove the values at the given indexes, preserving the order of the remaining values.
+    /// Returns a table that maps each old index to the new index of the value,
+    /// or to `None` if the value was removed.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexerRefs;
+    /// # fn main() {
+    /// let mut di = DupIndexerRefs::<String>::new();
+    /// di.insert_ref("foo");
+    /// di.insert_ref("bar");
+    /// di.insert_ref("baz");
+    /// assert_eq!(di.remove_many([0, 2]), vec![None, Some(0), None]);
+    /// assert_eq!(di.into_vec(), vec!["bar"]);
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if any of the indexes is out of bounds.
+    pub fn remove_many<It: IntoIterator<Item = I>>(&mut self, indexes: It) -> Vec<Option<I>> {
+        let mut removed = vec![false; self.values.len()];
+        for index in indexes {
+            removed[index.index()] = true;
+        }
+        let mut removed = removed.into_iter();
+        self.retain(|_| removed.next() == Some(false))
+    }
+
+    /// Move all values of another indexer into this one, keeping the indexes of existing values.
+    /// Values that already exist in this indexer are dropped, and the new ones are appended in
+    /// the index order of `other`, without cloning. If counting is enabled for this indexer,
+    /// the counts of `other` are added to it, or 1 per value if `other` did not count insertions.
+    /// Returns a table that maps each index of `other` to the index of the value in this indexer.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexerRefs;
+    /// # fn main() {
+    /// let mut di = DupIndexerRefs::<String>::new();
+    /// di.insert_ref("foo");
+    /// di.insert_ref("bar");
+    /// let mut other = DupIndexerRefs::<String>::new();
+    /// other.insert_ref("baz");
+    /// other.insert_ref("foo");
+    /// assert_eq!(di.merge(other), vec![2, 0]);
+    /// assert_eq!(di.into_vec(), vec!["foo", "bar", "baz"]);
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn merge<S2, I2>(&mut self, other: DupIndexerRefs<T, S2, I2>) -> Vec<I> {
+        let mut counts = other.counts.map(Vec::into_iter);
+        other
+            .values
+            .into_iter()
+            .map(|value| {
+                let count = counts.as_mut().and_then(Iterator::next).unwrap_or(1);
+                let index = self.insert_owned_full(value).0;
+                if let Some(counts) = &mut self.counts {
+                    // `insert_owned_full` has already counted one insertion
+                    counts[index.index()] += count - 1;
+                }
+                index
+            })
+            .collect()
+    }
+
+    /// Insert all values of the iterator into the indexer, and return the index of each value
+    /// in the order of the iterator. This dictionary-encodes a sequence in a single pass:
+    /// the indexer keeps the unique values, and the returned vector keeps the code of each item.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexerRefs;
+    /// # fn main() {
+    /// let mut di = DupIndexerRefs::<String>::new();
+    /// let codes = di.extend_indices(["a", "b", "a"].map(String::from));
+    /// assert_eq!(codes, vec![0, 1, 0]);
+    /// assert_eq!(di.into_vec(), vec!["a", "b"]);
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn extend_indices<It: IntoIterator<Item = T>>(&mut self, iter: It) -> Vec<I> {
+        let iter = iter.into_iter();
+        let size_hint = iter.size_hint().0;
+        self.reserve_for(size_hint);
+        let mut indexes = Vec::with_capacity(size_hint);
+        indexes.extend(iter.map(|value| self.insert_owned(value)));
+        indexes
+    }
+
+    /// Reserve capacity for an iterator with the given lower size bound. Some of its values are
+    /// likely to be duplicates if the indexer is not empty, so only half of them are reserved.
+    fn reserve_for(&mut self, size_hint: usize) {
+        let additional = if self.values.is_empty() {
+            size_hint
+        } else {
+            size_hint - size_hint / 2
+        };
+        let additional = additional.min(self.max_len.saturating_sub(self.values.len()));
+        self.values.reserve(additional);
+        if let Some(counts) = &mut self.counts {
+            counts.reserve(additional);
+        }
+        let values = &self.values;
+        let hasher = &self.hasher;
+        self.lookup.reserve(additional, |&i| {
+            make_hash(hasher, values[i.index()].deref())
+        });
+    }
+
+    /// Converts the indexer into a read-only [`FrozenIndexer`] that uses a minimal perfect hash
+    /// function instead of a hash table, keeping the same indexes of all values.
+    /// The values of the frozen indexer can be looked up by any borrowed form of `T`, e.g. `&str`.
+    ///
+    /// # Panics
+    /// Panics if the perfect hash function cannot be built with any of the tried hash seeds.
+    /// Values with identical hashes are handled separately, so this is extremely unlikely.
+    #[must_use]
+    pub fn freeze(self) -> FrozenIndexer<T, S, I> {
+        FrozenIndexer::new(self.values, self.hasher)
+    }
+
+    /// Return the index of the value if it exists in the indexer, without inserting it.
+    /// The value may be `T::Target`, e.g. `&str` for `DupIndexerRefs<String>`,
+    /// or any other type that is [`Equivalent`] to it.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexerRefs;
+    /// # fn main() {
+    /// let mut di = DupIndexerRefs::<String>::new();
+    /// di.insert_ref("hello");
+    /// di.insert_ref("world");
+    /// assert_eq!(di.get_index_of("world"), Some(1));
+    /// assert_eq!(di.get_index_of("foo"), None);
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn get_index_of<Q>(&self, value: &Q) -> Option<I>
+    where
+        Q: Hash + Equivalent<T::Target> + ?Sized,
+    {
+        self.get_index_of_with_hash(make_hash(&self.hasher, value), value)
+    }
+
+    /// Same as [`DupIndexerRefs::get_index_of`], but uses a hash computed with
+    /// [`DupIndexerRefs::hash_of`] instead of hashing the value again.
+    #[must_use]
+    pub fn get_index_of_with_hash<Q>(&self, hash: u64, value: &Q) -> Option<I>
+    where
+        Q: Equivalent<T::Target> + ?Sized,
+    {
+        self.lookup
+            .find(hash, |&i| value.equivalent(self.values[i.index()].deref()))
+            .copied()
+    }
+
+    /// Compute the hash of a value with the hasher of this indexer. The hash can be computed once,
+    /// e.g. to pick a shard, and then reused with [`DupIndexerRefs::insert_owned_with_hash`],
+    /// [`DupIndexerRefs::insert_ref_with_hash`], or [`DupIndexerRefs::get_index_of_with_hash`].
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexerRefs;
+    /// # fn main() {
+    /// let mut di = DupIndexerRefs::<String>::new();
+    /// let hash = di.hash_of("hello");
+    /// assert_eq!(di.insert_ref_with_hash(hash, "hello"), 0);
+    /// assert_eq!(di.insert_owned_with_hash(hash, "hello".to_string()), 0);
+    /// assert_eq!(di.get_index_of_with_hash(hash, "hello"), Some(0));
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn hash_of<Q: Hash + ?Sized>(&self, value: &Q) -> u64 {
+        make_hash(&self.hasher, value)
+    }
+
+    /// Return true if the value exists in the indexer.
+    /// The value may be `T::Target`, or any other type that is [`Equivalent`] to it.
+    #[inline]
+    #[must_use]
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        Q: Hash + Equivalent<T::Target> + ?Sized,
+    {
+        self.get_index_of(value).is_some()
+    }
+
     /// Converts the indexer into a vector.
     #[inline]
     #[must_use]
@@ -121,6 +477,50 @@ where
         self.values
     }
 
+    /// Start counting how many times each value was inserted, including the duplicate insertions.
+    /// The values inserted before this call are counted as inserted once.
+    /// Use [`DupIndexerRefs::finish_by_frequency`] to get the values ordered by their counts.
+    pub fn enable_counts(&mut self) {
+        if self.counts.is_none() {
+            self.counts = Some(vec![1; self.values.len()]);
+        }
+    }
+
+    /// Returns the number of insertions of each value, or `None` if counting was not enabled
+    /// with [`DupIndexerRefs::enable_counts`].
+    #[inline]
+    #[must_use]
+    pub fn counts(&self) -> Option<&[usize]> {
+        self.counts.as_deref()
+    }
+
+    /// Converts the indexer into a vector of values sorted by the descending number of insertions,
+    /// so that the most frequent values get the smallest indexes, e.g. for a compact varint encoding.
+    /// Values with the same count keep their insertion order.
+    /// Also returns a table that maps each old index to the new index of the value.
+    /// If counting was not enabled with [`DupIndexerRefs::enable_counts`], the order is not changed.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexerRefs;
+    /// # fn main() {
+    /// let mut di = DupIndexerRefs::<String>::new();
+    /// di.enable_counts();
+    /// di.insert_ref("foo");
+    /// di.insert_ref("bar");
+    /// di.insert_ref("bar");
+    /// di.insert_ref("baz");
+    /// di.insert_ref("bar");
+    /// di.insert_ref("baz");
+    /// let (values, remap) = di.finish_by_frequency();
+    /// assert_eq!(values, vec!["bar", "baz", "foo"]);
+    /// assert_eq!(remap, vec![2, 0, 1]);
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn finish_by_frequency(self) -> (Vec<T>, Vec<I>) {
+        sort_by_frequency(self.values, self.counts)
+    }
+
     /// Insert a string value into the indexer if it doesn't already exist,
     /// and return the index of the value.
     ///
@@ -134,29 +534,135 @@ where
     /// assert_eq!(di.into_vec(), vec!["hello", "world"]);
     /// # }
     /// ```
-    pub fn insert_owned(&mut self, value: T) -> usize {
-        // This is safe because we own the value and will not modify or drop it,
-        // unless we consume the whole values vector,
-        // nor would we access the values in the vector before then.
-        // When dropping, index will be dropped without freeing the memory.
-        // create a static reference to the string, which will live as long as the program
-        let value_ref =
-            unsafe { std::mem::transmute::<&T::Target, &'static T::Target>(value.deref()) };
-
-        match self.lookup.entry(value_ref) {
-            Occupied(entry) => *entry.get(),
-            Vacant(entry) => {
-                let index = self.values.len();
-                entry.insert(index);
-                self.values.push(value);
-                index
-            }
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert_owned(&mut self, value: T) -> I {
+        self.insert_owned_full(value).0
+    }
+
+    /// Insert a value into the indexer if it doesn't already exist,
+    /// and return the index of the value, and `true` if the value was newly inserted.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexerRefs;
+    /// # fn main() {
+    /// let mut di = DupIndexerRefs::<String>::new();
+    /// assert_eq!(di.insert_owned_full("hello".to_string()), (0, true));
+    /// assert_eq!(di.insert_owned_full("hello".to_string()), (0, false));
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert_owned_full(&mut self, value: T) -> (I, bool) {
+        self.entry(value.deref()).insert_full(|| value)
+    }
+
+    /// Get the entry of the given key for insertion or lookup,
+    /// so that the owned value only needs to be created if it does not exist yet.
+    /// The key may be `T::Target`, or any other type that is [`Equivalent`] to it.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexerRefs;
+    /// # fn main() {
+    /// let mut di = DupIndexerRefs::<String>::new();
+    /// assert_eq!(di.entry("hello").or_insert_with(|| "hello".to_string()), 0);
+    /// assert_eq!(di.entry("world").or_insert_with(|| "world".to_string()), 1);
+    /// assert_eq!(di.entry("hello").or_insert_with(|| unreachable!()), 0);
+    /// assert_eq!(di.entry("foo").index(), None);
+    /// # }
+    /// ```
+    pub fn entry<Q>(&mut self, key: &Q) -> Entry<'_, T, I>
+    where
+        Q: Hash + Equivalent<T::Target> + ?Sized,
+    {
+        let hash = make_hash(&self.hasher, key);
+        self.entry_with_hash(hash, key)
+    }
+
+    fn entry_with_hash<Q>(&mut self, hash: u64, key: &Q) -> Entry<'_, T, I>
+    where
+        Q: Equivalent<T::Target> + ?Sized,
+    {
+        let values = &self.values;
+        let hasher = &self.hasher;
+        let entry = self.lookup.entry(
+            hash,
+            |&i| key.equivalent(values[i.index()].deref()),
+            |&i| make_hash(hasher, values[i.index()].deref()),
+        );
+        Entry::new(entry, &mut self.values, &mut self.counts, self.max_len)
+    }
+
+    /// Insert a value into the indexer if it doesn't already exist, and return the index of the value,
+    /// or return an error with the rejected value if the indexer is full.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexerRefs;
+    /// # fn main() {
+    /// let mut di = DupIndexerRefs::<String>::new();
+    /// di.set_max_len(1);
+    /// assert_eq!(di.try_insert_owned("hello".to_string()), Ok(0));
+    /// assert_eq!(di.try_insert_owned("hello".to_string()), Ok(0));
+    /// let err = di.try_insert_owned("world".to_string()).unwrap_err();
+    /// assert_eq!(err.into_value(), "world");
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`CapacityError`] if the number of values would exceed the maximum length
+    /// of the indexer, or the capacity of the index type `I`.
+    pub fn try_insert_owned(&mut self, value: T) -> Result<I, CapacityError<T>> {
+        let (index, _) = self
+            .entry(value.deref())
+            .try_insert_full(value, |value| value)?;
+        Ok(index)
+    }
+
+    /// Insert a cloneable value into the indexer if it doesn't already exist, and return the index
+    /// of the value, or return an error with the rejected reference if the indexer is full.
+    /// The value is only cloned if it is inserted.
+    ///
+    /// # Errors
+    /// Returns [`CapacityError`] if the number of values would exceed the maximum length
+    /// of the indexer, or the capacity of the index type `I`.
+    pub fn try_insert_ref<'a>(
+        &mut self,
+        value: &'a T::Target,
+    ) -> Result<I, CapacityError<&'a T::Target>> {
+        let (index, _) = self.entry(value).try_insert_full(value, T::from_target)?;
+        Ok(index)
+    }
+
+    /// Tries to reserve capacity for at least `additional` more values,
+    /// reporting an allocation failure instead of aborting.
+    ///
+    /// # Errors
+    /// Returns [`TryReserveError`] if the capacity overflows, or if the allocator reports a failure.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.values
+            .try_reserve(additional)
+            .map_err(TryReserveError::values)?;
+        if let Some(counts) = &mut self.counts {
+            counts
+                .try_reserve(additional)
+                .map_err(TryReserveError::values)?;
         }
+        let values = &self.values;
+        let hasher = &self.hasher;
+        self.lookup
+            .try_reserve(additional, |&i| {
+                make_hash(hasher, values[i.index()].deref())
+            })
+            .map_err(TryReserveError::lookup)
     }
 
     /// Insert a cloneable value into the indexer if it doesn't already exist,
-    /// and return the index of the value. Slightly slower than [`DupIndexerRefs::insert_owned`],
-    /// but allows value to be a reference that does not need to be cloned if it was already added.
+    /// and return the index of the value. Unlike [`DupIndexerRefs::insert_owned`],
+    /// this allows value to be a reference that does not need to be cloned if it was already added.
     ///
     /// ```
     /// # use dup_indexer::DupIndexerRefs;
@@ -168,34 +674,110 @@ where
     /// assert_eq!(di.into_vec(), vec!["hello", "world"]);
     /// # }
     /// ```
-    pub fn insert_ref(&mut self, value: &T::Target) -> usize {
-        match self.lookup.get(value) {
-            Some(index) => *index,
-            None => self.insert_owned(value.to_owned()),
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert_ref(&mut self, value: &T::Target) -> I {
+        self.insert_ref_full(value).0
+    }
+
+    /// Insert a cloneable value into the indexer if it doesn't already exist,
+    /// and return the index of the value, and `true` if the value was newly inserted.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexerRefs;
+    /// # fn main() {
+    /// let mut di = DupIndexerRefs::<String>::new();
+    /// assert_eq!(di.insert_ref_full("hello"), (0, true));
+    /// assert_eq!(di.insert_ref_full("hello"), (0, false));
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert_ref_full(&mut self, value: &T::Target) -> (I, bool) {
+        self.entry(value).insert_full(|| T::from_target(value))
+    }
+
+    /// Same as [`DupIndexerRefs::insert_owned`], but uses a hash computed with
+    /// [`DupIndexerRefs::hash_of`] instead of hashing the value again. If the hash does not match
+    /// the value, the indexer may store duplicate values, or fail to find them later.
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert_owned_with_hash(&mut self, hash: u64, value: T) -> I {
+        self.entry_with_hash(hash, value.deref())
+            .insert_full(|| value)
+            .0
+    }
+
+    /// Same as [`DupIndexerRefs::insert_ref`], but uses a hash computed with
+    /// [`DupIndexerRefs::hash_of`] instead of hashing the value again. If the hash does not match
+    /// the value, the indexer may store duplicate values, or fail to find them later.
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert_ref_with_hash(&mut self, hash: u64, value: &T::Target) -> I {
+        self.entry_with_hash(hash, value)
+            .insert_full(|| T::from_target(value))
+            .0
+    }
+}
+
+impl<T, S, I> FromIterator<T> for DupIndexerRefs<T, S, I>
+where
+    T: FromTarget + Eq + Hash,
+    T::Target: Eq + Hash,
+    S: BuildHasher + Default,
+    I: IndexType,
+{
+    fn from_iter<It: IntoIterator<Item = T>>(iter: It) -> Self {
+        let mut di = Self::with_hasher(S::default());
+        di.extend(iter);
+        di
+    }
+}
+
+impl<T, S, I> Extend<T> for DupIndexerRefs<T, S, I>
+where
+    T: FromTarget + Eq + Hash,
+    T::Target: Eq + Hash,
+    S: BuildHasher,
+    I: IndexType,
+{
+    fn extend<It: IntoIterator<Item = T>>(&mut self, iter: It) {
+        let iter = iter.into_iter();
+        self.reserve_for(iter.size_hint().0);
+        for value in iter {
+            self.insert_owned(value);
         }
     }
 }
 
-impl<T: StableDerefKey, S> Index<usize> for DupIndexerRefs<T, S> {
+impl<T, S, I: IndexType> Index<I> for DupIndexerRefs<T, S, I> {
     type Output = T;
 
     #[inline]
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.values[index]
+    fn index(&self, index: I) -> &Self::Output {
+        &self.values[index.index()]
     }
 }
 
-impl<T: StableDerefKey, S> IntoIterator for DupIndexerRefs<T, S> {
+impl<T, S, I> IntoIterator for DupIndexerRefs<T, S, I> {
     type Item = T;
-    type IntoIter = std::vec::IntoIter<T>;
+    type IntoIter = vec::IntoIter<T>;
 
     #[inline]
-    fn into_iter(self) -> std::vec::IntoIter<T> {
+    fn into_iter(self) -> vec::IntoIter<T> {
         self.values.into_iter()
     }
 }
 
-impl<T: StableDerefKey, S> Deref for DupIndexerRefs<T, S> {
+impl<T, S, I> Deref for DupIndexerRefs<T, S, I> {
     type Target = [T];
 
     #[inline]
@@ -204,8 +786,8 @@ impl<T: StableDerefKey, S> Deref for DupIndexerRefs<T, S> {
     }
 }
 
-impl<T: StableDerefKey + Debug, S> Debug for DupIndexerRefs<T, S> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+impl<T: Debug, S, I> Debug for DupIndexerRefs<T, S, I> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         f.debug_map()
             .entries(self.values.iter().enumerate())
             .finish()
@@ -246,6 +828,229 @@ mod tests {
         assert_eq!(di.into_vec(), vec!["foo", "bar"]);
     }
 
+    fn check_key_type<T>(a: &T::Target, b: &T::Target)
+    where
+        T: FromTarget + Eq + Hash,
+        T::Target: Eq + Hash,
+    {
+        let mut di: DupIndexerRefs<T> = DupIndexerRefs::new();
+        assert_eq!(di.insert_ref(a), 0);
+        assert_eq!(di.insert_owned(T::from_target(b)), 1);
+        assert_eq!(di.insert_ref(b), 1);
+        assert_eq!(di.insert_owned(T::from_target(a)), 0);
+        assert_eq!(di.get_index_of(b), Some(1));
+        assert_eq!(di.len(), 2);
+        let values = di.into_vec();
+        assert!(values[0].deref() == a);
+        assert!(values[1].deref() == b);
+    }
+
+    #[test]
+    fn test_key_types() {
+        check_key_type::<Box<str>>("foo", "bar");
+        check_key_type::<Vec<u8>>(b"foo", b"bar");
+        check_key_type::<Box<[u32]>>(&[1, 2], &[]);
+        check_key_type::<CString>(c"foo", c"bar");
+        check_key_type::<Rc<str>>("foo", "bar");
+        check_key_type::<Arc<str>>("foo", "bar");
+        #[cfg(feature = "std")]
+        {
+            check_key_type::<PathBuf>("foo/bar".as_ref(), "foo".as_ref());
+            check_key_type::<OsString>("foo".as_ref(), "bar".as_ref());
+        }
+    }
+
+    #[test]
+    fn test_to_owned_key_type() {
+        // A key type from before `FromTarget`, only related to its target by `ToOwned`
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct Name(String);
+
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct OwnedName(Box<Name>);
+
+        impl Deref for OwnedName {
+            type Target = Name;
+            fn deref(&self) -> &Name {
+                &self.0
+            }
+        }
+
+        impl core::borrow::Borrow<Name> for OwnedName {
+            fn borrow(&self) -> &Name {
+                &self.0
+            }
+        }
+
+        impl ToOwned for Name {
+            type Owned = OwnedName;
+            fn to_owned(&self) -> OwnedName {
+                OwnedName(Box::new(Name(self.0.clone())))
+            }
+        }
+
+        #[allow(deprecated)]
+        unsafe impl StableDerefKey for OwnedName {}
+
+        check_key_type::<OwnedName>(&Name("foo".to_string()), &Name("bar".to_string()));
+    }
+
+    #[test]
+    fn test_insert_full() {
+        let mut di: DupIndexerRefs<String> = DupIndexerRefs::new();
+        assert_eq!(di.insert_owned_full("foo".to_string()), (0, true));
+        assert_eq!(di.insert_ref_full("bar"), (1, true));
+        assert_eq!(di.insert_ref_full("foo"), (0, false));
+        assert_eq!(di.insert_owned_full("bar".to_string()), (1, false));
+        assert_eq!(di.into_vec(), vec!["foo", "bar"]);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut di: DupIndexerRefs<String> = DupIndexerRefs::new();
+        for value in ["a", "bb", "c", "dd", "e"] {
+            di.insert_ref(value);
+        }
+        let remap = di.retain(|v| v.len() == 1);
+        assert_eq!(remap, vec![Some(0), None, Some(1), None, Some(2)]);
+        assert_eq!(di.as_slice(), &["a", "c", "e"]);
+        assert_eq!(di.get_index_of("e"), Some(2));
+        assert_eq!(di.get_index_of("bb"), None);
+        assert_eq!(di.insert_ref("bb"), 3);
+        assert_eq!(di.insert_owned("c".to_string()), 1);
+
+        assert_eq!(di.retain(|_| false), vec![None; 4]);
+        assert!(di.is_empty());
+        assert_eq!(di.insert_ref("a"), 0);
+    }
+
+    #[test]
+    fn test_remove_many() {
+        let mut di: DupIndexerRefs<String, DefaultHashBuilder, u32> = DupIndexerRefs::default();
+        for value in ["a", "b", "c", "d", "e"] {
+            di.insert_ref(value);
+        }
+        let remap = di.remove_many([3, 1, 3]);
+        assert_eq!(remap, vec![Some(0), None, Some(1), None, Some(2)]);
+        assert_eq!(di.as_slice(), &["a", "c", "e"]);
+        assert_eq!(di.get_index_of("e"), Some(2));
+        assert_eq!(di.insert_ref("b"), 3);
+        assert_eq!(di.remove_many([]), vec![Some(0), Some(1), Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut di: DupIndexerRefs<String, DefaultHashBuilder, u16> = DupIndexerRefs::default();
+        di.insert_ref("a");
+        di.insert_ref("b");
+        let mut other: DupIndexerRefs<String, RandomState, u8> =
+            DupIndexerRefs::with_hasher(RandomState::new());
+        for value in ["c", "b", "d", "c"] {
+            other.insert_ref(value);
+        }
+        assert_eq!(di.merge(other), vec![2, 1, 3]);
+        assert_eq!(di.as_slice(), &["a", "b", "c", "d"]);
+        assert_eq!(di.get_index_of("d"), Some(3));
+        assert_eq!(di.insert_ref("c"), 2);
+    }
+
+    #[test]
+    fn test_from_iter_and_extend() {
+        let mut di: DupIndexerRefs<String, RandomState, u16> =
+            ["a", "b", "a"].map(String::from).into_iter().collect();
+        assert_eq!(di.len(), 2);
+        di.extend(["c", "b"].map(String::from));
+        assert_eq!(di.extend_indices(["d", "a"].map(String::from)), vec![3, 0]);
+        assert_eq!(di.as_slice(), &["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut di: DupIndexerRefs<String> = DupIndexerRefs::new();
+        di.enable_counts();
+        assert_eq!(di.entry("foo").or_insert_with(|| "foo".to_string()), 0);
+        assert_eq!(di.entry("foo").or_insert_with(|| unreachable!()), 0);
+        match di.entry("bar") {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(entry) => assert_eq!(entry.insert("bar".to_string()), 1),
+        }
+        match di.entry("bar") {
+            Entry::Occupied(entry) => assert_eq!(entry.into_ref(), "bar"),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(di.insert_ref("foo"), 0);
+        assert_eq!(di.counts(), Some([3, 1].as_slice()));
+    }
+
+    #[test]
+    fn test_max_len() {
+        let mut di: DupIndexerRefs<String> = DupIndexerRefs::new();
+        di.set_max_len(2);
+        assert_eq!(di.try_insert_ref("foo"), Ok(0));
+        assert_eq!(di.try_insert_owned("bar".to_string()), Ok(1));
+        assert_eq!(di.try_insert_ref("foo"), Ok(0));
+        assert_eq!(
+            di.try_insert_ref("baz").map_err(CapacityError::into_value),
+            Err("baz")
+        );
+        let err = di.try_insert_owned("baz".to_string()).unwrap_err();
+        assert_eq!(err.into_value(), "baz");
+        assert!(di.try_reserve(10).is_ok());
+        assert!(di.try_reserve(usize::MAX).is_err());
+        assert_eq!(di.as_slice(), &["foo", "bar"]);
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut di: DupIndexerRefs<String> = DupIndexerRefs::new();
+        assert_eq!(di.insert_ref("a"), 0);
+        assert_eq!(di.counts(), None);
+        di.enable_counts();
+        assert_eq!(di.counts(), Some([1].as_slice()));
+        assert_eq!(di.insert_ref("b"), 1);
+        assert_eq!(di.insert_ref("c"), 2);
+        assert_eq!(di.insert_ref("c"), 2);
+        assert_eq!(di.insert_ref("d"), 3);
+        assert_eq!(di.insert_ref("d"), 3);
+        assert_eq!(di.insert_ref("d"), 3);
+        assert_eq!(di.counts(), Some([1, 1, 2, 3].as_slice()));
+        assert_eq!(di.remove_many([1]), vec![Some(0), None, Some(1), Some(2)]);
+        assert_eq!(di.counts(), Some([1, 2, 3].as_slice()));
+        let (values, remap) = di.finish_by_frequency();
+        assert_eq!(values, vec!["d", "c", "a"]);
+        assert_eq!(remap, vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut di: DupIndexerRefs<String> = DupIndexerRefs::new();
+        assert_eq!(di.get_index_of("foo"), None);
+        assert!(!di.contains("foo"));
+        assert_eq!(di.get(0), None);
+        assert_eq!(di.insert_ref("foo"), 0);
+        assert_eq!(di.insert_owned("bar".to_string()), 1);
+        assert_eq!(di.get_index_of("foo"), Some(0));
+        assert_eq!(di.get_index_of("bar"), Some(1));
+        assert_eq!(di.get_index_of("baz"), None);
+        assert!(di.contains("bar"));
+        assert!(!di.contains("baz"));
+        assert_eq!(di.get(1).map(String::as_str), Some("bar"));
+        assert_eq!(di.get(2), None);
+        assert_eq!(di.len(), 2);
+    }
+
+    #[test]
+    fn test_index_type() {
+        let mut di: DupIndexerRefs<String, DefaultHashBuilder, u16> = DupIndexerRefs::default();
+        assert_eq!(di.insert_owned("foo".to_string()), 0_u16);
+        assert_eq!(di.insert_ref("bar"), 1_u16);
+        assert_eq!(di.insert_ref("foo"), 0_u16);
+        assert_eq!(di.get_index_of("bar"), Some(1_u16));
+        assert_eq!(di[1_u16], "bar");
+        assert_eq!(di.get(0_u16).map(String::as_str), Some("foo"));
+        assert_eq!(di.into_vec(), vec!["foo", "bar"]);
+    }
+
     #[test]
     fn test_custom_hasher() {
         let mut di: DupIndexerRefs<String, RandomState> =
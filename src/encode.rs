@@ -0,0 +1,161 @@
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+use core::hash::Hash;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::DupIndexer;
+
+/// The minimum number of values each thread of the parallel encoders indexes on its own,
+/// so that small slices are not split into chunks that cost more to merge than to index.
+#[cfg(feature = "rayon")]
+const MIN_CHUNK_LEN: usize = 16 * 1024;
+
+/// Dictionary-encode a slice: return the unique values in the order of their first appearance,
+/// and the index of each value of the slice in that list of unique values.
+/// Only the unique values are cloned.
+///
+/// ```
+/// # use dup_indexer::encode;
+/// # fn main() {
+/// let (dict, codes) = encode(&[10, 20, 10, 30, 20]);
+/// assert_eq!(dict, vec![10, 20, 30]);
+/// assert_eq!(codes, vec![0, 1, 0, 2, 1]);
+/// # }
+/// ```
+#[must_use]
+pub fn encode<T: Eq + Hash + Clone>(values: &[T]) -> (Vec<T>, Vec<usize>) {
+    let (dict, codes) = encode_with(values, |value| value);
+    (dict.into_iter().cloned().collect(), codes)
+}
+
+/// Dictionary-encode a slice of references, same as [`encode`], but converting
+/// the unique values into owned values, e.g. `&str` into `String`.
+///
+/// ```
+/// # use dup_indexer::encode_ref;
+/// # fn main() {
+/// let (dict, codes) = encode_ref(&["foo", "bar", "foo"]);
+/// assert_eq!(dict, vec!["foo".to_string(), "bar".to_string()]);
+/// assert_eq!(codes, vec![0, 1, 0]);
+/// # }
+/// ```
+#[must_use]
+pub fn encode_ref<Q: Eq + Hash + ToOwned + ?Sized>(values: &[&Q]) -> (Vec<Q::Owned>, Vec<usize>) {
+    let (dict, codes) = encode_with(values, |&value| value);
+    (dict.into_iter().map(ToOwned::to_owned).collect(), codes)
+}
+
+/// Same as [`encode`], but splits the slice into chunks that are indexed in parallel,
+/// then merges the dictionaries of all chunks and rewrites the codes of each chunk.
+/// The result is identical to the result of [`encode`].
+///
+/// ```
+/// # use dup_indexer::par_encode;
+/// # fn main() {
+/// let values: Vec<u32> = (0..100_000).map(|v| v % 7).collect();
+/// let (dict, codes) = par_encode(&values);
+/// assert_eq!(dict, vec![0, 1, 2, 3, 4, 5, 6]);
+/// assert_eq!(codes[..8], [0, 1, 2, 3, 4, 5, 6, 0]);
+/// # }
+/// ```
+#[cfg(feature = "rayon")]
+#[must_use]
+pub fn par_encode<T: Eq + Hash + Clone + Sync>(values: &[T]) -> (Vec<T>, Vec<usize>) {
+    let (dict, codes) = par_encode_with(values, |value| value);
+    (dict.into_iter().cloned().collect(), codes)
+}
+
+/// Same as [`encode_ref`], but indexes chunks of the slice in parallel, see [`par_encode`].
+#[cfg(feature = "rayon")]
+#[must_use]
+pub fn par_encode_ref<Q: Eq + Hash + ToOwned + Sync + ?Sized>(
+    values: &[&Q],
+) -> (Vec<Q::Owned>, Vec<usize>) {
+    let (dict, codes) = par_encode_with(values, |&value| value);
+    (dict.into_iter().map(ToOwned::to_owned).collect(), codes)
+}
+
+/// Dictionary-encode the keys of the values, without cloning any of them.
+fn encode_with<'a, V, K: Eq + Hash + ?Sized>(
+    values: &'a [V],
+    key: impl Fn(&'a V) -> &'a K,
+) -> (Vec<&'a K>, Vec<usize>) {
+    let mut di = DupIndexer::new();
+    let codes = di.extend_indices(values.iter().map(key));
+    (di.into_vec(), codes)
+}
+
+#[cfg(feature = "rayon")]
+fn par_encode_with<'a, V: Sync, K: Eq + Hash + Sync + ?Sized>(
+    values: &'a [V],
+    key: impl Fn(&'a V) -> &'a K + Sync,
+) -> (Vec<&'a K>, Vec<usize>) {
+    let threads = rayon::current_num_threads();
+    let chunk_len = MIN_CHUNK_LEN.max((values.len() + threads - 1) / threads);
+    if values.len() <= chunk_len {
+        return encode_with(values, key);
+    }
+
+    // Index each chunk on its own, writing the local codes directly into the result
+    let mut codes = alloc::vec![0; values.len()];
+    let dicts: Vec<DupIndexer<&K>> = values
+        .par_chunks(chunk_len)
+        .zip(codes.par_chunks_mut(chunk_len))
+        .map(|(values, codes)| {
+            let mut di = DupIndexer::new();
+            for (value, code) in values.iter().zip(codes) {
+                *code = di.insert(key(value));
+            }
+            di
+        })
+        .collect();
+
+    // Merge the dictionaries in the order of the chunks, so that the result matches `encode`
+    let mut di = DupIndexer::with_capacity(dicts.first().map_or(0, DupIndexer::len));
+    let remaps: Vec<Vec<usize>> = dicts.into_iter().map(|d| di.merge(d)).collect();
+
+    codes
+        .par_chunks_mut(chunk_len)
+        .zip(remaps)
+        .for_each(|(codes, remap)| {
+            for code in codes {
+                *code = remap[*code];
+            }
+        });
+    (di.into_vec(), codes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let values: Vec<String> = ["b", "a", "b", "c", "a"].map(String::from).to_vec();
+        let (dict, codes) = encode(&values);
+        assert_eq!(dict, vec!["b", "a", "c"]);
+        assert_eq!(codes, vec![0, 1, 0, 2, 1]);
+        assert_eq!(encode::<u8>(&[]), (vec![], vec![]));
+
+        let (dict, codes) = encode_ref(&["x", "y", "y"]);
+        assert_eq!(dict, vec!["x".to_string(), "y".to_string()]);
+        assert_eq!(codes, vec![0, 1, 1]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_encode() {
+        let values: Vec<u64> = (0..200_000_u64).map(|v| (v * 7919) % 1013).collect();
+        assert_eq!(par_encode(&values), encode(&values));
+        assert_eq!(par_encode(&values[..10]), encode(&values[..10]));
+
+        let strings: Vec<String> = values.iter().map(u64::to_string).collect();
+        let refs: Vec<&str> = strings.iter().map(String::as_str).collect();
+        let (dict, codes) = par_encode_ref(&refs);
+        assert_eq!((dict.clone(), codes.clone()), encode_ref(&refs));
+        assert_eq!(dict.len(), 1013);
+        assert!(codes.iter().zip(&refs).all(|(&code, &s)| dict[code] == s));
+    }
+}
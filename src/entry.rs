@@ -0,0 +1,206 @@
+use alloc::vec::Vec;
+use hashbrown::hash_table;
+
+use crate::index::next_index;
+use crate::{CapacityError, IndexType};
+
+/// A view into a single value of an indexer, which may either exist or be vacant.
+/// Created by [`DupIndexer::entry`](crate::DupIndexer::entry)
+/// or [`DupIndexerRefs::entry`](crate::DupIndexerRefs::entry).
+///
+/// ```
+/// # use dup_indexer::{DupIndexer, Entry};
+/// # fn main() {
+/// let mut di = DupIndexer::<String>::new();
+/// assert_eq!(di.entry("Hello").or_insert_with(|| "Hello".to_string()), 0);
+/// match di.entry("Hello") {
+///     Entry::Occupied(entry) => assert_eq!(entry.index(), 0),
+///     Entry::Vacant(_) => unreachable!(),
+/// }
+/// # }
+/// ```
+pub enum Entry<'a, T, I = usize> {
+    /// The value already exists in the indexer.
+    Occupied(OccupiedEntry<'a, T, I>),
+    /// The value does not exist in the indexer yet.
+    Vacant(VacantEntry<'a, T, I>),
+}
+
+/// A view into a value that exists in the indexer. It is a part of the [`Entry`] enum.
+pub struct OccupiedEntry<'a, T, I> {
+    index: I,
+    value: &'a T,
+    counts: &'a mut Option<Vec<usize>>,
+}
+
+/// A view into a value that does not exist in the indexer yet. It is a part of the [`Entry`] enum.
+pub struct VacantEntry<'a, T, I> {
+    entry: hash_table::VacantEntry<'a, I>,
+    values: &'a mut Vec<T>,
+    counts: &'a mut Option<Vec<usize>>,
+    max_len: usize,
+}
+
+impl<'a, T, I: IndexType> Entry<'a, T, I> {
+    /// Create an entry from the result of a hash table lookup.
+    pub(crate) fn new(
+        entry: hash_table::Entry<'a, I>,
+        values: &'a mut Vec<T>,
+        counts: &'a mut Option<Vec<usize>>,
+        max_len: usize,
+    ) -> Self {
+        match entry {
+            hash_table::Entry::Occupied(entry) => {
+                let index = *entry.get();
+                Entry::Occupied(OccupiedEntry {
+                    index,
+                    value: &values[index.index()],
+                    counts,
+                })
+            }
+            hash_table::Entry::Vacant(entry) => Entry::Vacant(VacantEntry {
+                entry,
+                values,
+                counts,
+                max_len,
+            }),
+        }
+    }
+
+    /// Return the index of the value if it exists in the indexer.
+    #[must_use]
+    pub fn index(&self) -> Option<I> {
+        match self {
+            Entry::Occupied(entry) => Some(entry.index()),
+            Entry::Vacant(_) => None,
+        }
+    }
+
+    /// Return the index of the value, inserting the given value if it does not exist yet.
+    /// The value must be equal to the key used to create the entry.
+    ///
+    /// # Panics
+    /// Panics if the indexer is full, see [`VacantEntry::insert`].
+    pub fn or_insert(self, value: T) -> I {
+        self.insert_full(|| value).0
+    }
+
+    /// Return the index of the value, inserting the value computed by the given function
+    /// if it does not exist yet. The function is only called if the value is vacant,
+    /// and it must return a value equal to the key used to create the entry.
+    ///
+    /// # Panics
+    /// Panics if the indexer is full, see [`VacantEntry::insert`].
+    pub fn or_insert_with<F: FnOnce() -> T>(self, make_value: F) -> I {
+        self.insert_full(make_value).0
+    }
+
+    /// Return the index of the value, inserting the value computed by the given function
+    /// if it does not exist yet, and `true` if the value was newly inserted.
+    /// Unlike [`OccupiedEntry::index`], this counts an existing value as another insertion
+    /// for the indexer insertion counts.
+    pub(crate) fn insert_full<F: FnOnce() -> T>(self, make_value: F) -> (I, bool) {
+        match self {
+            Entry::Occupied(entry) => (entry.count_insertion(), false),
+            Entry::Vacant(entry) => (entry.insert(make_value()), true),
+        }
+    }
+
+    /// Same as [`Entry::insert_full`], but if the indexer is full, returns an error
+    /// with the given value instead of converting it with `into_value` and inserting it.
+    pub(crate) fn try_insert_full<V>(
+        self,
+        value: V,
+        into_value: impl FnOnce(V) -> T,
+    ) -> Result<(I, bool), CapacityError<V>> {
+        match self {
+            Entry::Occupied(entry) => Ok((entry.count_insertion(), false)),
+            Entry::Vacant(entry) => match entry.next_index() {
+                Some(index) => Ok((entry.push(index, into_value(value)), true)),
+                None => Err(CapacityError::new(value)),
+            },
+        }
+    }
+}
+
+impl<'a, T, I: IndexType> OccupiedEntry<'a, T, I> {
+    /// Return the index of the value.
+    #[inline]
+    #[must_use]
+    pub fn index(&self) -> I {
+        self.index
+    }
+
+    /// Return a reference to the value stored in the indexer.
+    #[inline]
+    #[must_use]
+    pub fn get(&self) -> &T {
+        self.value
+    }
+
+    /// Convert the entry into a reference to the value stored in the indexer.
+    #[inline]
+    #[must_use]
+    pub fn into_ref(self) -> &'a T {
+        self.value
+    }
+
+    fn count_insertion(self) -> I {
+        if let Some(counts) = self.counts {
+            counts[self.index.index()] += 1;
+        }
+        self.index
+    }
+}
+
+impl<T, I: IndexType> VacantEntry<'_, T, I> {
+    /// Insert the value into the indexer and return its index.
+    /// The value must be equal to the key used to create the entry,
+    /// otherwise the indexer will not be able to find it later.
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert(self, value: T) -> I {
+        assert!(
+            self.values.len() < self.max_len,
+            "the number of values exceeds the maximum length of the indexer"
+        );
+        let index = next_index(self.values.len());
+        self.push(index, value)
+    }
+
+    /// Insert the value into the indexer and return its index, or return an error
+    /// with the value if the indexer is full.
+    /// The value must be equal to the key used to create the entry,
+    /// otherwise the indexer will not be able to find it later.
+    ///
+    /// # Errors
+    /// Returns [`CapacityError`] if the number of values would exceed the maximum length
+    /// of the indexer, or the capacity of the index type `I`.
+    pub fn try_insert(self, value: T) -> Result<I, CapacityError<T>> {
+        match self.next_index() {
+            Some(index) => Ok(self.push(index, value)),
+            None => Err(CapacityError::new(value)),
+        }
+    }
+
+    /// Return the index of the next value, or `None` if the indexer is full.
+    fn next_index(&self) -> Option<I> {
+        let len = self.values.len();
+        if len < self.max_len {
+            I::try_from_usize(len)
+        } else {
+            None
+        }
+    }
+
+    fn push(self, index: I, value: T) -> I {
+        self.entry.insert(index);
+        self.values.push(value);
+        if let Some(counts) = self.counts {
+            counts.push(1);
+        }
+        index
+    }
+}
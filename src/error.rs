@@ -0,0 +1,93 @@
+use core::fmt::{Debug, Display, Formatter};
+
+/// The error returned when a new value cannot be inserted because the indexer is full,
+/// either because it reached the maximum length set with `set_max_len`,
+/// or because the index type cannot represent any more indexes.
+/// The rejected value can be taken back with [`CapacityError::into_value`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct CapacityError<T> {
+    value: T,
+}
+
+impl<T> CapacityError<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Self { value }
+    }
+
+    /// Returns a reference to the value that could not be inserted.
+    #[inline]
+    #[must_use]
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns the value that could not be inserted.
+    #[inline]
+    #[must_use]
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Debug for CapacityError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CapacityError").finish_non_exhaustive()
+    }
+}
+
+impl<T> Display for CapacityError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str("the indexer is full, the value cannot be inserted")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> std::error::Error for CapacityError<T> {}
+
+/// The error returned by `try_reserve` when the memory for the values
+/// or for the lookup table could not be allocated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TryReserveError {
+    kind: TryReserveErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TryReserveErrorKind {
+    Values(alloc::collections::TryReserveError),
+    Lookup(hashbrown::TryReserveError),
+}
+
+impl TryReserveError {
+    pub(crate) fn values(err: alloc::collections::TryReserveError) -> Self {
+        Self {
+            kind: TryReserveErrorKind::Values(err),
+        }
+    }
+
+    pub(crate) fn lookup(err: hashbrown::TryReserveError) -> Self {
+        Self {
+            kind: TryReserveErrorKind::Lookup(err),
+        }
+    }
+}
+
+impl Display for TryReserveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match &self.kind {
+            TryReserveErrorKind::Values(err) => write!(f, "unable to reserve values: {err}"),
+            TryReserveErrorKind::Lookup(err) => {
+                write!(f, "unable to reserve lookup table: {err:?}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TryReserveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            TryReserveErrorKind::Values(err) => Some(err),
+            TryReserveErrorKind::Lookup(_) => None,
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use core::cmp::Ordering;
+use core::fmt::{Debug, Display, Formatter};
+use core::hash::{Hash, Hasher};
+
+#[allow(deprecated)]
+use crate::PtrRead;
+
+macro_rules! total_float {
+    ($(#[$doc:meta])* $name:ident, $float:ty) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, Default)]
+        pub struct $name($float);
+
+        impl $name {
+            #[doc = concat!("Wrap a `", stringify!($float), "` value as is, keeping its exact bits.")]
+            #[inline]
+            #[must_use]
+            pub fn new(value: $float) -> Self {
+                Self(value)
+            }
+
+            #[doc = concat!("Wrap a `", stringify!($float), "` value, replacing any `NaN` with the canonical `", stringify!($float), "::NAN`,")]
+            /// and `-0.0` with `0.0`, so that all `NaN`s are equal, and both zeros are equal.
+            #[inline]
+            #[must_use]
+            pub fn canonical(value: $float) -> Self {
+                Self(value).canonical_nan().canonical_zero()
+            }
+
+            #[doc = concat!("Replace any `NaN`, regardless of its sign and payload, with the canonical `", stringify!($float), "::NAN`.")]
+            #[inline]
+            #[must_use]
+            pub fn canonical_nan(self) -> Self {
+                if self.0.is_nan() {
+                    Self(<$float>::NAN)
+                } else {
+                    self
+                }
+            }
+
+            /// Replace `-0.0` with `0.0`.
+            #[inline]
+            #[must_use]
+            pub fn canonical_zero(self) -> Self {
+                if self.0 == 0.0 {
+                    Self(0.0)
+                } else {
+                    self
+                }
+            }
+
+            #[doc = concat!("Returns the wrapped `", stringify!($float), "` value.")]
+            #[inline]
+            #[must_use]
+            pub fn get(self) -> $float {
+                self.0
+            }
+        }
+
+        impl PartialEq for $name {
+            #[inline]
+            fn eq(&self, other: &Self) -> bool {
+                self.0.to_bits() == other.0.to_bits()
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl Hash for $name {
+            #[inline]
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.to_bits().hash(state);
+            }
+        }
+
+        impl PartialOrd for $name {
+            #[inline]
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $name {
+            #[inline]
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.total_cmp(&other.0)
+            }
+        }
+
+        impl From<$float> for $name {
+            #[inline]
+            fn from(value: $float) -> Self {
+                Self::new(value)
+            }
+        }
+
+        impl From<$name> for $float {
+            #[inline]
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl Debug for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+                Debug::fmt(&self.0, f)
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+                Display::fmt(&self.0, f)
+            }
+        }
+
+        #[allow(deprecated)]
+        unsafe impl PtrRead for $name {}
+    };
+}
+
+total_float!(
+    /// An `f32` that implements `Eq`, `Hash`, and `Ord`, so that it can be used as a value of the indexers.
+    ///
+    /// Values are equal if they have the same bits, and are ordered with [`f32::total_cmp`].
+    /// By default, `NaN`s with different payloads and `-0.0` / `0.0` are all distinct values.
+    /// Use [`TotalF32::canonical`], or [`TotalF32::canonical_nan`] and [`TotalF32::canonical_zero`],
+    /// to treat them as the same value.
+    ///
+    /// ```
+    /// # use dup_indexer::{DupIndexer, TotalF32};
+    /// # fn main() {
+    /// let mut di = DupIndexer::new();
+    /// assert_eq!(di.insert(TotalF32::canonical(0.0)), 0);
+    /// assert_eq!(di.insert(TotalF32::canonical(-0.0)), 0);
+    /// assert_eq!(di.insert(TotalF32::canonical(f32::NAN)), 1);
+    /// assert_eq!(di.insert(TotalF32::new(-0.0)), 2);
+    /// # }
+    /// ```
+    TotalF32,
+    f32
+);
+
+total_float!(
+    /// An `f64` that implements `Eq`, `Hash`, and `Ord`, so that it can be used as a value of the indexers.
+    ///
+    /// Values are equal if they have the same bits, and are ordered with [`f64::total_cmp`].
+    /// By default, `NaN`s with different payloads and `-0.0` / `0.0` are all distinct values.
+    /// Use [`TotalF64::canonical`], or [`TotalF64::canonical_nan`] and [`TotalF64::canonical_zero`],
+    /// to treat them as the same value.
+    ///
+    /// ```
+    /// # use dup_indexer::{DupIndexer, TotalF64};
+    /// # fn main() {
+    /// let mut di = DupIndexer::new();
+    /// assert_eq!(di.insert(TotalF64::new(1.5)), 0);
+    /// assert_eq!(di.insert(TotalF64::new(2.5)), 1);
+    /// assert_eq!(di.insert(1.5.into()), 0);
+    /// let values: Vec<f64> = di.into_iter().map(f64::from).collect();
+    /// assert_eq!(values, vec![1.5, 2.5]);
+    /// # }
+    /// ```
+    TotalF64,
+    f64
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DupIndexer;
+
+    #[test]
+    fn test_nan_and_zero() {
+        let other_nan = f64::from_bits(f64::NAN.to_bits() ^ 1);
+        assert!(other_nan.is_nan());
+        assert_ne!(TotalF64::new(f64::NAN), TotalF64::new(other_nan));
+        assert_ne!(TotalF64::new(f64::NAN), TotalF64::new(-f64::NAN));
+        assert_eq!(TotalF64::new(f64::NAN), TotalF64::new(f64::NAN));
+        assert_eq!(
+            TotalF64::new(-f64::NAN).canonical_nan(),
+            TotalF64::new(other_nan).canonical_nan()
+        );
+        assert_ne!(TotalF64::new(0.0), TotalF64::new(-0.0));
+        assert_eq!(TotalF64::new(-0.0).canonical_zero(), TotalF64::new(0.0));
+        assert_ne!(
+            TotalF64::new(-0.0).canonical_nan(),
+            TotalF64::new(0.0).canonical_nan()
+        );
+        assert_eq!(TotalF32::canonical(-0.0), TotalF32::canonical(0.0));
+        assert_eq!(
+            TotalF32::canonical(-f32::NAN).get().to_bits(),
+            f32::NAN.to_bits()
+        );
+    }
+
+    #[test]
+    fn test_order() {
+        let mut values: Vec<TotalF32> = [2.0, f32::NAN, -0.0, 0.0, f32::NEG_INFINITY, -1.0]
+            .into_iter()
+            .map(TotalF32::new)
+            .collect();
+        values.sort();
+        assert_eq!(format!("{values:?}"), "[-inf, -1.0, -0.0, 0.0, 2.0, NaN]");
+    }
+
+    #[test]
+    fn test_indexer() {
+        let mut di: DupIndexer<TotalF64> = DupIndexer::new();
+        for value in [1.0, f64::NAN, -0.0, 1.0, -f64::NAN, 0.0, 2.0] {
+            di.insert(TotalF64::canonical(value));
+        }
+        assert_eq!(format!("{di:?}"), "{0: 1.0, 1: NaN, 2: 0.0, 3: 2.0}");
+        assert_eq!(di.get_index_of(&TotalF64::canonical(-0.0)), Some(2));
+        assert_eq!(di.get_index_of(&TotalF64::new(-0.0)), None);
+    }
+}
@@ -0,0 +1,461 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Formatter};
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+use core::ops::{Deref, Index};
+
+use hashbrown::Equivalent;
+
+use crate::{DefaultHashBuilder, IndexType};
+
+/// The average number of values per bucket of the perfect hash function.
+const BUCKET_SIZE: usize = 4;
+
+/// The number of hash seeds to try before giving up on building the perfect hash function.
+const MAX_SEEDS: u64 = 32;
+
+/// A read-only indexer created by [`DupIndexer::freeze`](crate::DupIndexer::freeze)
+/// or [`DupIndexerRefs::freeze`](crate::DupIndexerRefs::freeze).
+///
+/// Instead of a hash table, it uses a minimal perfect hash function over the values,
+/// which only needs a few bytes per value to find the index of any value in constant time.
+/// The values keep the same indexes as in the original indexer.
+/// Different values with the same hash cannot be separated by the perfect hash function,
+/// so they are kept in a small sorted list instead, and compared one by one.
+///
+/// ```
+/// # use dup_indexer::DupIndexer;
+/// # fn main() {
+/// let mut di = DupIndexer::<String>::new();
+/// di.insert("hello".to_string());
+/// di.insert("world".to_string());
+/// let frozen = di.freeze();
+/// assert_eq!(frozen.get_index_of("world"), Some(1));
+/// assert_eq!(frozen.get_index_of("foo"), None);
+/// assert_eq!(frozen[0], "hello");
+/// # }
+/// ```
+pub struct FrozenIndexer<T, S = DefaultHashBuilder, I = usize> {
+    values: Vec<T>,
+    hasher: S,
+    seed: u64,
+    /// The `(d1, d2)` displacements of each bucket of the perfect hash function.
+    displacements: Vec<(u32, u32)>,
+    /// Maps each slot of the perfect hash function to the index of its value.
+    slots: PackedIndexes,
+    /// The `(hash, index)` of the values whose hash is already used by another value, sorted by hash.
+    collisions: Vec<(u64, usize)>,
+    _index: PhantomData<fn() -> I>,
+}
+
+impl<T: Eq + Hash, S: BuildHasher, I: IndexType> FrozenIndexer<T, S, I> {
+    /// Build the perfect hash function over unique values.
+    ///
+    /// # Panics
+    /// Panics if the perfect hash function cannot be built with any of the tried hash seeds.
+    /// Values with identical hashes are handled separately, so this is extremely unlikely.
+    pub(crate) fn new(values: Vec<T>, hasher: S) -> Self {
+        for seed in 0..MAX_SEEDS {
+            let mut entries: Vec<(u64, usize)> = values
+                .iter()
+                .enumerate()
+                .map(|(index, v)| (seeded_hash(&hasher, seed, v), index))
+                .collect();
+            entries.sort_unstable();
+            // Only the first value of each hash goes into the perfect hash function
+            let mut collisions = Vec::new();
+            entries.dedup_by(|value, first| {
+                let is_dup = value.0 == first.0;
+                if is_dup {
+                    collisions.push(*value);
+                }
+                is_dup
+            });
+            if let Some((displacements, slots)) = build_perfect_hash(&entries, values.len()) {
+                return Self {
+                    values,
+                    hasher,
+                    seed,
+                    displacements,
+                    slots,
+                    collisions,
+                    _index: PhantomData,
+                };
+            }
+        }
+        panic!("unable to build a perfect hash function");
+    }
+
+    /// Return the index of the value if it exists in the indexer.
+    /// The value may be any [`Equivalent`] type, same as for
+    /// [`DupIndexer::get_index_of`](crate::DupIndexer::get_index_of).
+    #[must_use]
+    pub fn get_index_of<Q>(&self, value: &Q) -> Option<I>
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+    {
+        if self.values.is_empty() {
+            return None;
+        }
+        let hash = seeded_hash(&self.hasher, self.seed, value);
+        let bucket = bucket_of(hash, self.displacements.len());
+        let (d1, d2) = self.displacements[bucket];
+        let index = self.slots.get(slot_of(hash, d1, d2, self.slots.len()));
+        if value.equivalent(&self.values[index]) {
+            return I::try_from_usize(index);
+        }
+        let start = self.collisions.partition_point(|&(h, _)| h < hash);
+        self.collisions[start..]
+            .iter()
+            .take_while(|&&(h, _)| h == hash)
+            .find(|&&(_, index)| value.equivalent(&self.values[index]))
+            .and_then(|&(_, index)| I::try_from_usize(index))
+    }
+
+    /// Return true if the value exists in the indexer.
+    /// The value may be any [`Equivalent`] type, see [`FrozenIndexer::get_index_of`].
+    #[inline]
+    #[must_use]
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+    {
+        self.get_index_of(value).is_some()
+    }
+}
+
+impl<T, S, I: IndexType> FrozenIndexer<T, S, I> {
+    /// Extracts a slice containing the entire indexer values.
+    #[inline]
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        self
+    }
+
+    /// Get the number of values in the indexer.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Return true if the indexer is empty.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns a reference to the value at the given index, or `None` if it is out of bounds.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, index: I) -> Option<&T> {
+        self.values.get(index.index())
+    }
+
+    /// Converts the indexer into a vector.
+    #[inline]
+    #[must_use]
+    pub fn into_vec(self) -> Vec<T> {
+        self.values
+    }
+}
+
+impl<T, S, I: IndexType> Index<I> for FrozenIndexer<T, S, I> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, index: I) -> &Self::Output {
+        &self.values[index.index()]
+    }
+}
+
+impl<T, S, I> IntoIterator for FrozenIndexer<T, S, I> {
+    type Item = T;
+    type IntoIter = vec::IntoIter<T>;
+
+    #[inline]
+    fn into_iter(self) -> vec::IntoIter<T> {
+        self.values.into_iter()
+    }
+}
+
+impl<T, S, I> Deref for FrozenIndexer<T, S, I> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        &self.values
+    }
+}
+
+impl<T: Debug, S, I> Debug for FrozenIndexer<T, S, I> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_map()
+            .entries(self.values.iter().enumerate())
+            .finish()
+    }
+}
+
+fn seeded_hash<Q: Hash + ?Sized, S: BuildHasher>(hasher: &S, seed: u64, value: &Q) -> u64 {
+    let mut state = hasher.build_hasher();
+    state.write_u64(seed);
+    value.hash(&mut state);
+    state.finish()
+}
+
+/// The `SplitMix64` finalizer, used to derive an independent second hash from the first one.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn bucket_of(hash: u64, buckets: usize) -> usize {
+    // The result is less than `buckets`, so it always fits into usize
+    ((hash >> 32) % buckets as u64) as usize
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn slot_of(hash: u64, d1: u32, d2: u32, len: usize) -> usize {
+    let f1 = hash & 0xFFFF_FFFF;
+    let f2 = mix(hash);
+    let slot = f2
+        .wrapping_add(u64::from(d1).wrapping_mul(f1))
+        .wrapping_add(u64::from(d2));
+    // The result is less than `len`, so it always fits into usize
+    (slot % len as u64) as usize
+}
+
+/// Build a minimal perfect hash function using the "hash, displace, and compress" algorithm:
+/// the values are split into buckets, and starting with the largest bucket, each bucket gets
+/// the first displacement that puts all of its values into unused slots.
+/// Each slot stores the value index paired with its unique hash, which must be less than `values_len`.
+/// Returns `None` if some bucket could not be placed, in which case another seed should be used.
+fn build_perfect_hash(
+    entries: &[(u64, usize)],
+    values_len: usize,
+) -> Option<(Vec<(u32, u32)>, PackedIndexes)> {
+    let len = entries.len();
+    let hashes: Vec<u64> = entries.iter().map(|&(hash, _)| hash).collect();
+    let buckets_len = (len + BUCKET_SIZE - 1) / BUCKET_SIZE;
+    let mut buckets = vec![Vec::new(); buckets_len];
+    for (index, &hash) in hashes.iter().enumerate() {
+        buckets[bucket_of(hash, buckets_len)].push(index);
+    }
+    let mut order: Vec<usize> = (0..buckets_len).collect();
+    order.sort_by_key(|&bucket| core::cmp::Reverse(buckets[bucket].len()));
+
+    let mut displacements = vec![(0, 0); buckets_len];
+    let mut slots = PackedIndexes::new(len, values_len);
+    let mut used = vec![false; len];
+    let mut next_free = 0;
+    let mut bucket_slots = Vec::with_capacity(BUCKET_SIZE);
+    for bucket in order {
+        let indexes = &buckets[bucket];
+        match indexes.len() {
+            0 => break,
+            1 => {
+                // A single value can be placed directly into the next free slot
+                while used[next_free] {
+                    next_free += 1;
+                }
+                let offset = slot_of(hashes[indexes[0]], 0, 0, len);
+                let d2 = u32::try_from((next_free + len - offset) % len).ok()?;
+                displacements[bucket] = (0, d2);
+                bucket_slots.clear();
+                bucket_slots.push(next_free);
+            }
+            _ => {
+                displacements[bucket] =
+                    find_displacement(&hashes, indexes, &used, &mut bucket_slots)?;
+            }
+        }
+        for (&index, &slot) in indexes.iter().zip(&bucket_slots) {
+            used[slot] = true;
+            slots.set(slot, entries[index].1);
+        }
+    }
+    Some((displacements, slots))
+}
+
+/// Find the displacement that puts all values of the bucket into distinct unused slots,
+/// storing the found slots in `bucket_slots`.
+fn find_displacement(
+    hashes: &[u64],
+    indexes: &[usize],
+    used: &[bool],
+    bucket_slots: &mut Vec<usize>,
+) -> Option<(u32, u32)> {
+    let len = used.len();
+    let max_displacement = u32::try_from(len).unwrap_or(u32::MAX);
+    for d1 in 0..max_displacement {
+        for d2 in 0..max_displacement {
+            bucket_slots.clear();
+            for &index in indexes {
+                let slot = slot_of(hashes[index], d1, d2, len);
+                if used[slot] || bucket_slots.contains(&slot) {
+                    break;
+                }
+                bucket_slots.push(slot);
+            }
+            if bucket_slots.len() == indexes.len() {
+                return Some((d1, d2));
+            }
+        }
+        if d1 == 0 && has_colliding_values(hashes, indexes, len) {
+            // No displacement can separate these values, try another seed
+            return None;
+        }
+    }
+    None
+}
+
+/// Check if any two values of the bucket always map to the same slot regardless of the displacement.
+fn has_colliding_values(hashes: &[u64], indexes: &[usize], len: usize) -> bool {
+    let parts = |index: usize| {
+        (
+            slot_of(hashes[index], 0, 0, len),
+            slot_of(hashes[index], 1, 0, len),
+        )
+    };
+    indexes
+        .iter()
+        .enumerate()
+        .any(|(i, &a)| indexes[..i].iter().any(|&b| parts(a) == parts(b)))
+}
+
+/// A fixed-size array of indexes, each stored with the minimal number of bits.
+struct PackedIndexes {
+    len: usize,
+    width: u32,
+    words: Vec<u64>,
+}
+
+impl PackedIndexes {
+    /// Create an array of `len` zeros, each large enough to store any value less than `bound`.
+    fn new(len: usize, bound: usize) -> Self {
+        let width = (usize::BITS - bound.saturating_sub(1).leading_zeros()).max(1);
+        let bits = len * width as usize;
+        Self {
+            len,
+            width,
+            words: vec![0; (bits + 63) / 64],
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn set(&mut self, index: usize, value: usize) {
+        let value = value as u64;
+        let bit = index * self.width as usize;
+        let (word, offset) = (bit / 64, bit % 64);
+        self.words[word] |= value << offset;
+        if offset + self.width as usize > 64 {
+            self.words[word + 1] |= value >> (64 - offset);
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn get(&self, index: usize) -> usize {
+        let bit = index * self.width as usize;
+        let (word, offset) = (bit / 64, bit % 64);
+        let mut value = self.words[word] >> offset;
+        if offset + self.width as usize > 64 {
+            value |= self.words[word + 1] << (64 - offset);
+        }
+        let mask = u64::MAX >> (64 - self.width);
+        // The stored values were created from a usize
+        (value & mask) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DupIndexer, DupIndexerRefs};
+
+    #[test]
+    fn test_frozen() {
+        let mut di: DupIndexer<String> = DupIndexer::new();
+        for value in 0..300 {
+            di.insert(value.to_string());
+        }
+        let frozen = di.freeze();
+        assert_eq!(frozen.len(), 300);
+        for value in 0..300 {
+            assert_eq!(frozen.get_index_of(value.to_string().as_str()), Some(value));
+        }
+        assert_eq!(frozen.get_index_of("foo"), None);
+        assert!(frozen.contains("42"));
+        assert!(!frozen.contains("300"));
+        assert_eq!(frozen[42], "42");
+        assert_eq!(frozen.get(299).map(String::as_str), Some("299"));
+        assert_eq!(frozen.get(300), None);
+        assert_eq!(frozen.as_slice()[7], "7");
+        assert_eq!(frozen.into_vec().len(), 300);
+    }
+
+    #[test]
+    fn test_frozen_small() {
+        let frozen = DupIndexer::<i32>::new().freeze();
+        assert!(frozen.is_empty());
+        assert_eq!(frozen.get_index_of(&0), None);
+
+        let mut di: DupIndexerRefs<String, DefaultHashBuilder, u8> = DupIndexerRefs::default();
+        di.insert_ref("foo");
+        let frozen = di.freeze();
+        assert_eq!(frozen.get_index_of("foo"), Some(0_u8));
+        assert_eq!(frozen.get_index_of("bar"), None);
+        assert_eq!(frozen[0_u8], "foo");
+        assert_eq!(format!("{frozen:?}"), r#"{0: "foo"}"#);
+    }
+
+    #[test]
+    fn test_frozen_identical_hashes() {
+        // Different values with identical hashes cannot be separated by any hash seed
+        #[derive(Debug, PartialEq, Eq)]
+        struct BadHash(u32);
+
+        impl Hash for BadHash {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                (self.0 % 50).hash(state);
+            }
+        }
+
+        let mut di: DupIndexer<BadHash> = DupIndexer::new();
+        for value in 0..60 {
+            di.insert(BadHash(value));
+        }
+        let frozen = di.freeze();
+        for value in 0..60 {
+            assert_eq!(frozen.get_index_of(&BadHash(value)), Some(value as usize));
+        }
+        assert_eq!(frozen.get_index_of(&BadHash(60)), None);
+
+        let mut di: DupIndexer<BadHash> = DupIndexer::new();
+        di.insert(BadHash(1));
+        di.insert(BadHash(51));
+        let frozen = di.freeze();
+        assert_eq!(frozen.get_index_of(&BadHash(51)), Some(1));
+        assert_eq!(frozen.get_index_of(&BadHash(1)), Some(0));
+        assert_eq!(frozen.get_index_of(&BadHash(101)), None);
+    }
+
+    #[test]
+    fn test_packed_indexes() {
+        for len in [1, 2, 3, 63, 64, 65, 1000] {
+            let mut packed = PackedIndexes::new(len, len);
+            for index in 0..len {
+                packed.set(index, len - 1 - index);
+            }
+            for index in 0..len {
+                assert_eq!(packed.get(index), len - 1 - index);
+            }
+        }
+    }
+}
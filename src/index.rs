@@ -0,0 +1,147 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+/// A type that can be used as an index of the values stored in a [`DupIndexer`](crate::DupIndexer)
+/// or a [`DupIndexerRefs`](crate::DupIndexerRefs), instead of the default `usize`.
+///
+/// Smaller integer types like `u32` or `u16` reduce the memory used by the lookup map,
+/// and custom newtypes make it impossible to mix up the indexes of different indexers.
+///
+/// ```
+/// use dup_indexer::{DefaultHashBuilder, DupIndexer, IndexType};
+///
+/// #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// struct KeyId(u32);
+///
+/// impl IndexType for KeyId {
+///     fn try_from_usize(index: usize) -> Option<Self> {
+///         u32::try_from(index).ok().map(KeyId)
+///     }
+///
+///     fn index(self) -> usize {
+///         self.0 as usize
+///     }
+/// }
+///
+/// let mut di: DupIndexer<String, DefaultHashBuilder, KeyId> = DupIndexer::default();
+/// assert_eq!(di.insert("hello".to_string()), KeyId(0));
+/// assert_eq!(di.insert("world".to_string()), KeyId(1));
+/// assert_eq!(di.insert("hello".to_string()), KeyId(0));
+/// assert_eq!(di[KeyId(1)], "world");
+/// ```
+pub trait IndexType: Copy {
+    /// Create an index from a `usize`, or return `None` if the value does not fit into `Self`.
+    fn try_from_usize(index: usize) -> Option<Self>;
+
+    /// Convert the index into a `usize`. This must return the same value
+    /// that was used to create the index with [`IndexType::try_from_usize`].
+    /// Indexes that do not fit into `usize` should return `usize::MAX`, so that they are out of bounds.
+    fn index(self) -> usize;
+}
+
+macro_rules! impl_index_type {
+    ($($t:ty),*) => {
+        $(
+            impl IndexType for $t {
+                #[inline]
+                fn try_from_usize(index: usize) -> Option<Self> {
+                    Self::try_from(index).ok()
+                }
+
+                #[inline]
+                fn index(self) -> usize {
+                    // A value that does not fit into `usize` cannot be a valid index,
+                    // e.g. a `u64` on 32-bit targets, so treat it as out of bounds.
+                    usize::try_from(self).unwrap_or(usize::MAX)
+                }
+            }
+        )*
+    };
+}
+
+impl_index_type![u8, u16, u32, u64, usize];
+
+/// Convert the index of the next value into the index type.
+///
+/// # Panics
+/// Panics if the index does not fit into `I`.
+#[inline]
+pub(crate) fn next_index<I: IndexType>(len: usize) -> I {
+    match I::try_from_usize(len) {
+        Some(index) => index,
+        None => panic!("the number of values exceeds the capacity of the index type"),
+    }
+}
+
+/// Build a table mapping old indexes to the new ones after removing some values,
+/// where `keep` yields `true` for every value that is retained, in the original order.
+pub(crate) fn compact_remap<I: IndexType>(keep: impl Iterator<Item = bool>) -> Vec<Option<I>> {
+    let mut new_len = 0;
+    keep.map(|keep| {
+        keep.then(|| {
+            let index = next_index(new_len);
+            new_len += 1;
+            index
+        })
+    })
+    .collect()
+}
+
+/// Sort values by the descending number of insertions, keeping the insertion order for equal counts,
+/// and return them together with a table that maps each old index to the new one.
+/// If `counts` is `None`, all values are treated as inserted once.
+pub(crate) fn sort_by_frequency<T, I: IndexType>(
+    values: Vec<T>,
+    counts: Option<Vec<usize>>,
+) -> (Vec<T>, Vec<I>) {
+    let mut entries: Vec<(usize, T)> = values.into_iter().enumerate().collect();
+    if let Some(counts) = counts {
+        entries.sort_by_key(|(old_index, _)| Reverse(counts[*old_index]));
+    }
+    let mut remap = vec![0; entries.len()];
+    let values = entries
+        .into_iter()
+        .enumerate()
+        .map(|(new_index, (old_index, value))| {
+            remap[old_index] = new_index;
+            value
+        })
+        .collect();
+    (values, remap.into_iter().map(next_index).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_type() {
+        assert_eq!(u8::try_from_usize(255), Some(255));
+        assert_eq!(u8::try_from_usize(256), None);
+        assert_eq!(u16::try_from_usize(65_536), None);
+        assert_eq!(u32::try_from_usize(42).map(IndexType::index), Some(42));
+        assert_eq!(usize::try_from_usize(usize::MAX), Some(usize::MAX));
+        assert_eq!(u64::from(u32::MAX).index(), u32::MAX as usize);
+        #[cfg(target_pointer_width = "32")]
+        assert_eq!((u64::from(u32::MAX) + 1).index(), usize::MAX);
+    }
+
+    #[test]
+    fn test_compact_remap() {
+        let remap: Vec<Option<u8>> = compact_remap([true, false, false, true, true].into_iter());
+        assert_eq!(remap, vec![Some(0), None, None, Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn test_sort_by_frequency() {
+        let values = vec!['a', 'b', 'c', 'd'];
+        let (sorted, remap) = sort_by_frequency::<_, u16>(values.clone(), Some(vec![1, 3, 1, 2]));
+        assert_eq!(sorted, vec!['b', 'd', 'a', 'c']);
+        assert_eq!(remap, vec![2, 0, 3, 1]);
+
+        let (sorted, remap) = sort_by_frequency::<_, usize>(values, None);
+        assert_eq!(sorted, vec!['a', 'b', 'c', 'd']);
+        assert_eq!(remap, vec![0, 1, 2, 3]);
+    }
+}
@@ -1,16 +1,82 @@
 #![doc = include_str!("../README.md")]
+#![cfg_attr(all(not(test), not(feature = "std")), no_std)]
+
+extern crate alloc;
+
+#[cfg(not(any(feature = "foldhash", feature = "std")))]
+compile_error!(
+    "either the `foldhash` or the `std` feature must be enabled to provide the default hasher"
+);
 
 #[cfg(feature = "foldhash")]
 pub use foldhash;
 
+/// The default hasher of all indexers: `foldhash` if the `foldhash` feature is enabled,
+/// and the standard library hasher otherwise.
 #[cfg(feature = "foldhash")]
 pub type DefaultHashBuilder = foldhash::fast::RandomState;
 
-#[cfg(not(feature = "foldhash"))]
+/// The default hasher of all indexers: `foldhash` if the `foldhash` feature is enabled,
+/// and the standard library hasher otherwise.
+#[cfg(all(not(feature = "foldhash"), feature = "std"))]
 pub type DefaultHashBuilder = std::collections::hash_map::RandomState;
 
+use core::hash::{BuildHasher, Hash, Hasher};
+
+/// Hash a value with the given hasher, same as `BuildHasher::hash_one` which requires Rust 1.71.
+#[inline]
+pub(crate) fn make_hash<Q: Hash + ?Sized, S: BuildHasher>(hasher: &S, value: &Q) -> u64 {
+    let mut state = hasher.build_hasher();
+    value.hash(&mut state);
+    state.finish()
+}
+
+/// Lets the indexers look up values by a different type, e.g. by `&str` for `String` values.
+/// It is implemented for all borrowed forms of a type, and can be implemented for composite
+/// query types like a struct of `&str` fields, see [`DupIndexer::entry`].
+pub use hashbrown::Equivalent;
+
 mod owner;
 pub use owner::*;
 
 mod deref;
 pub use deref::*;
+
+mod float;
+pub use float::{TotalF32, TotalF64};
+
+mod index;
+pub use index::IndexType;
+
+#[cfg(feature = "std")]
+mod concurrent;
+#[cfg(feature = "std")]
+pub use concurrent::ConcurrentDupIndexer;
+
+mod error;
+pub use error::{CapacityError, TryReserveError};
+
+mod entry;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+
+mod str_indexer;
+pub use str_indexer::DupStrIndexer;
+
+#[cfg(target_has_atomic = "ptr")]
+mod arc_indexer;
+#[cfg(target_has_atomic = "ptr")]
+pub use arc_indexer::ArcDupIndexer;
+
+mod lru;
+pub use lru::LruDupIndexer;
+
+mod frozen;
+pub use frozen::FrozenIndexer;
+
+mod encode;
+pub use encode::{encode, encode_ref};
+#[cfg(feature = "rayon")]
+pub use encode::{par_encode, par_encode_ref};
+
+#[cfg(feature = "serde")]
+mod serde_impl;
@@ -0,0 +1,337 @@
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt::{Debug, Formatter};
+use core::hash::{BuildHasher, Hash};
+use core::ops::Index;
+
+use hashbrown::{Equivalent, HashTable};
+
+use crate::index::next_index;
+use crate::{make_hash, DefaultHashBuilder, IndexType};
+
+/// Marks the absence of a previous or next slot in the recency list.
+const NONE: usize = usize::MAX;
+
+/// A value together with its neighbors in the recency list.
+struct Slot<T> {
+    value: T,
+    /// The slot that was used more recently than this one.
+    prev: usize,
+    /// The slot that was used less recently than this one.
+    next: usize,
+}
+
+/// An indexer that holds at most a fixed number of values, evicting the least recently used value
+/// when a new value is inserted into a full indexer. The new value reuses the index of the evicted one,
+/// so all indexes are always less than the capacity.
+///
+/// Every insertion returns the evicted value, if any, so that the caller can invalidate its index.
+/// Inserting a value that already exists marks it as the most recently used.
+///
+/// ```
+/// # use dup_indexer::LruDupIndexer;
+/// # fn main() {
+/// let mut di = LruDupIndexer::<String>::new(2);
+/// assert_eq!(di.insert_ref("foo"), (0, None));
+/// assert_eq!(di.insert_ref("bar"), (1, None));
+/// assert_eq!(di.insert_ref("foo"), (0, None));
+/// // "bar" is the least recently used value, so it gets evicted
+/// assert_eq!(di.insert_ref("baz"), (1, Some("bar".to_string())));
+/// assert_eq!(di.get_index_of("bar"), None);
+/// assert_eq!(di[1], "baz");
+/// # }
+/// ```
+pub struct LruDupIndexer<T, S = DefaultHashBuilder, I = usize> {
+    slots: Vec<Slot<T>>,
+    /// Indexes into `slots`, hashed and compared using the values they point to.
+    lookup: HashTable<I>,
+    hasher: S,
+    capacity: usize,
+    /// The most recently used slot.
+    head: usize,
+    /// The least recently used slot, which is evicted first.
+    tail: usize,
+}
+
+impl<T> LruDupIndexer<T> {
+    /// Create a new instance of `LruDupIndexer<T>` that holds at most `capacity` values.
+    ///
+    /// # Panics
+    /// Panics if the capacity is zero.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<T, S: BuildHasher, I: IndexType> LruDupIndexer<T, S, I> {
+    /// Create a new instance of `LruDupIndexer<T>` that holds at most `capacity` values,
+    /// using the provided hasher.
+    ///
+    /// # Panics
+    /// Panics if the capacity is zero, or if it exceeds the capacity of the index type `I`.
+    #[must_use]
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self {
+        assert!(capacity > 0, "the capacity must be greater than zero");
+        let _: I = next_index(capacity - 1);
+        Self {
+            slots: Vec::new(),
+            lookup: HashTable::new(),
+            hasher,
+            capacity,
+            head: NONE,
+            tail: NONE,
+        }
+    }
+
+    /// Returns the maximum number of values the indexer can hold.
+    #[inline]
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the number of values in the indexer.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Return true if the indexer is empty.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Returns a reference to the value at the given index, or `None` if it is out of bounds.
+    /// This does not change the order of eviction.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, index: I) -> Option<&T> {
+        self.slots.get(index.index()).map(|slot| &slot.value)
+    }
+
+    /// Returns an iterator over all values in the order of their indexes.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.slots.iter().map(|slot| &slot.value)
+    }
+
+    /// Converts the indexer into a vector, where each value is stored at its index.
+    #[must_use]
+    pub fn into_vec(self) -> Vec<T> {
+        self.slots.into_iter().map(|slot| slot.value).collect()
+    }
+
+    /// Mark the slot as the most recently used.
+    fn touch(&mut self, slot: usize) {
+        if self.head != slot {
+            self.unlink(slot);
+            self.push_front(slot);
+        }
+    }
+
+    fn unlink(&mut self, slot: usize) {
+        let Slot { prev, next, .. } = self.slots[slot];
+        if prev == NONE {
+            self.head = next;
+        } else {
+            self.slots[prev].next = next;
+        }
+        if next == NONE {
+            self.tail = prev;
+        } else {
+            self.slots[next].prev = prev;
+        }
+    }
+
+    fn push_front(&mut self, slot: usize) {
+        self.slots[slot].prev = NONE;
+        self.slots[slot].next = self.head;
+        if self.head == NONE {
+            self.tail = slot;
+        } else {
+            self.slots[self.head].prev = slot;
+        }
+        self.head = slot;
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher, I: IndexType> LruDupIndexer<T, S, I> {
+    /// Insert a value into the indexer if it doesn't already exist, and return the index of the value,
+    /// together with the value that was evicted to make room for it, if any.
+    /// The evicted value had the same index as the newly inserted one.
+    pub fn insert(&mut self, value: T) -> (I, Option<T>) {
+        self.insert_with::<T, T>(value, |value| value)
+    }
+
+    /// Insert a value into the indexer if it doesn't already exist, and return the index of the value,
+    /// together with the value that was evicted to make room for it, if any.
+    /// The value is only cloned if it does not already exist in the indexer.
+    pub fn insert_ref<Q>(&mut self, value: &Q) -> (I, Option<T>)
+    where
+        Q: Hash + Equivalent<T> + ToOwned<Owned = T> + ?Sized,
+    {
+        self.insert_with::<Q, &Q>(value, ToOwned::to_owned)
+    }
+
+    /// Return the index of the value if it exists in the indexer, without inserting it.
+    /// This does not change the order of eviction.
+    /// The value may be any [`Equivalent`] type, same as for
+    /// [`DupIndexer::get_index_of`](crate::DupIndexer::get_index_of).
+    #[must_use]
+    pub fn get_index_of<Q>(&self, value: &Q) -> Option<I>
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+    {
+        let hash = make_hash(&self.hasher, value);
+        self.lookup
+            .find(hash, |&i| value.equivalent(&self.slots[i.index()].value))
+            .copied()
+    }
+
+    /// Return true if the value exists in the indexer.
+    /// This does not change the order of eviction.
+    /// The value may be any [`Equivalent`] type, see [`LruDupIndexer::get_index_of`].
+    #[inline]
+    #[must_use]
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+    {
+        self.get_index_of(value).is_some()
+    }
+
+    /// Find the value and mark it as the most recently used, or insert the value converted by
+    /// `into_value`, evicting the least recently used value if the indexer is full.
+    fn insert_with<Q, V>(&mut self, value: V, into_value: impl FnOnce(V) -> T) -> (I, Option<T>)
+    where
+        V: Borrow<Q>,
+        Q: Hash + Equivalent<T> + ?Sized,
+    {
+        let hash = make_hash(&self.hasher, value.borrow());
+        let slots = &self.slots;
+        let found = self
+            .lookup
+            .find(hash, |&i| {
+                value.borrow().equivalent(&slots[i.index()].value)
+            })
+            .copied();
+        if let Some(index) = found {
+            self.touch(index.index());
+            return (index, None);
+        }
+
+        let value = into_value(value);
+        let (slot, evicted) = if self.slots.len() < self.capacity {
+            self.slots.push(Slot {
+                value,
+                prev: NONE,
+                next: NONE,
+            });
+            (self.slots.len() - 1, None)
+        } else {
+            let slot = self.tail;
+            let evicted_hash = make_hash(&self.hasher, &self.slots[slot].value);
+            if let Ok(entry) = self.lookup.find_entry(evicted_hash, |&i| i.index() == slot) {
+                entry.remove();
+            }
+            self.unlink(slot);
+            let evicted = core::mem::replace(&mut self.slots[slot].value, value);
+            (slot, Some(evicted))
+        };
+        self.push_front(slot);
+
+        // The capacity was checked to fit into the index type when the indexer was created
+        let index = next_index(slot);
+        let slots = &self.slots;
+        let hasher = &self.hasher;
+        self.lookup
+            .insert_unique(hash, index, |&i| make_hash(hasher, &slots[i.index()].value));
+        (index, evicted)
+    }
+}
+
+impl<T, S, I: IndexType> Index<I> for LruDupIndexer<T, S, I> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, index: I) -> &Self::Output {
+        &self.slots[index.index()].value
+    }
+}
+
+impl<T: Debug, S, I> Debug for LruDupIndexer<T, S, I> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_map()
+            .entries(self.slots.iter().map(|slot| &slot.value).enumerate())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::RandomState;
+
+    use super::*;
+
+    #[test]
+    fn test_lru() {
+        let mut di: LruDupIndexer<String> = LruDupIndexer::new(3);
+        assert!(di.is_empty());
+        assert_eq!(di.capacity(), 3);
+        assert_eq!(di.insert("a".to_string()), (0, None));
+        assert_eq!(di.insert_ref("b"), (1, None));
+        assert_eq!(di.insert_ref("c"), (2, None));
+        assert_eq!(di.insert_ref("a"), (0, None));
+        assert_eq!(di.insert_ref("d"), (1, Some("b".to_string())));
+        assert_eq!(di.insert_ref("e"), (2, Some("c".to_string())));
+        assert_eq!(di.insert_ref("d"), (1, None));
+        assert_eq!(di.insert_ref("f"), (0, Some("a".to_string())));
+        assert_eq!(di.get_index_of("e"), Some(2));
+        assert!(!di.contains("a"));
+        assert_eq!(di.get(1).map(String::as_str), Some("d"));
+        assert_eq!(di.get(3), None);
+        assert_eq!(di.len(), 3);
+        assert_eq!(format!("{di:?}"), r#"{0: "f", 1: "d", 2: "e"}"#);
+        // get_index_of did not touch "e", so it is still the least recently used
+        assert_eq!(di.insert_ref("g"), (2, Some("e".to_string())));
+        assert_eq!(di.iter().collect::<Vec<_>>(), vec!["f", "d", "g"]);
+        assert_eq!(di.into_vec(), vec!["f", "d", "g"]);
+    }
+
+    #[test]
+    fn test_single_slot() {
+        let mut di: LruDupIndexer<i32, RandomState, u8> =
+            LruDupIndexer::with_hasher(1, RandomState::new());
+        assert_eq!(di.insert(1), (0, None));
+        assert_eq!(di.insert(1), (0, None));
+        assert_eq!(di.insert(2), (0, Some(1)));
+        assert_eq!(di.insert(3), (0, Some(2)));
+        assert_eq!(di[0], 3);
+        assert_eq!(di.get_index_of(&2), None);
+    }
+
+    #[test]
+    fn test_many_values() {
+        let mut di: LruDupIndexer<u32, RandomState, u8> =
+            LruDupIndexer::with_hasher(256, RandomState::new());
+        for value in 0..1000 {
+            let (index, evicted) = di.insert(value);
+            assert_eq!(u32::from(index), value % 256);
+            assert_eq!(evicted, value.checked_sub(256));
+        }
+        for value in 744..1000 {
+            assert_eq!(di.get_index_of(&value).map(u32::from), Some(value % 256));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the capacity of the index type")]
+    fn test_capacity_overflow() {
+        let _: LruDupIndexer<u32, RandomState, u8> =
+            LruDupIndexer::with_hasher(257, RandomState::new());
+    }
+}
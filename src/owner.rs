@@ -1,87 +1,111 @@
-use std::collections::hash_map::Entry::{Occupied, Vacant};
-use std::collections::{BTreeMap, BTreeSet, HashMap};
-use std::fmt::{Debug, Formatter};
-use std::hash::{BuildHasher, Hash};
-use std::mem::ManuallyDrop;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
-use std::num::{
+use alloc::borrow::{Cow, ToOwned};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Formatter};
+use core::hash::{BuildHasher, Hash};
+use core::num::{
     NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
     NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize, Wrapping,
 };
-use std::ops::{Deref, Index};
-use std::path::PathBuf;
-use std::ptr;
-use std::time::{Duration, SystemTime};
+use core::ops::{Deref, Index};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use hashbrown::{Equivalent, HashTable};
 
-use crate::DefaultHashBuilder;
+use crate::index::{compact_remap, sort_by_frequency};
+use crate::{
+    make_hash, CapacityError, DefaultHashBuilder, Entry, FrozenIndexer, IndexType, TryReserveError,
+};
 
-/// A value that can be used as a key in a [`DupIndexer`], which will copy its content
-/// using the [`ptr::read`] function, while also owning it internally.
+/// A value that could be used as a key in the older versions of [`DupIndexer`], which copied
+/// its content using the [`core::ptr::read`] function, while also owning it internally.
+///
+/// `DupIndexer` now only stores indexes in its lookup table, so it accepts any `Eq + Hash` value,
+/// and this trait is no longer required. It is kept for backward compatibility.
 ///
 /// # Safety
 /// Implementing this trait is unsafe because the implementation must guarantee that
 /// the value can be copied by copying the bits of the value assuming that the value
 /// itself is valid and readonly. All Copy types are `PtrRead`, but `Box<T>` is not.
+#[deprecated(note = "DupIndexer accepts any `Eq + Hash` value, and no longer uses this trait")]
 pub unsafe trait PtrRead {}
 
 macro_rules! impl_trait {
     ($($t:ty),*) => {
         $(
+            #[allow(deprecated)]
             unsafe impl PtrRead for $t {}
         )*
     };
 }
 
 impl_trait![(), &'static str];
-impl_trait![f32, f64, bool, char, String, PathBuf];
-impl_trait![SystemTime, Duration, Ipv4Addr, Ipv6Addr, IpAddr];
+impl_trait![f32, f64, bool, char, String, Duration];
+#[cfg(feature = "std")]
+impl_trait![PathBuf, SystemTime, Ipv4Addr, Ipv6Addr, IpAddr];
 impl_trait![u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize];
 impl_trait![NonZeroU8, NonZeroU16, NonZeroU32];
 impl_trait![NonZeroU64, NonZeroU128, NonZeroUsize];
 impl_trait![NonZeroI8, NonZeroI16, NonZeroI32];
 impl_trait![NonZeroI64, NonZeroI128, NonZeroIsize];
 
-unsafe impl<T: PtrRead> PtrRead for [T] {}
-unsafe impl<T: PtrRead, const N: usize> PtrRead for [T; N] {}
-unsafe impl<T: PtrRead> PtrRead for Wrapping<T> {}
-unsafe impl<T: PtrRead> PtrRead for Option<T> {}
-unsafe impl<T: PtrRead> PtrRead for Vec<T> {}
-unsafe impl<T: PtrRead, V: PtrRead, S> PtrRead for HashMap<T, V, S> {}
-unsafe impl<T: PtrRead, V: PtrRead> PtrRead for BTreeMap<T, V> {}
-unsafe impl<T: PtrRead> PtrRead for BTreeSet<T> {}
+#[allow(deprecated)]
+const _: () = {
+    unsafe impl<T: PtrRead> PtrRead for [T] {}
+    unsafe impl<T: PtrRead, const N: usize> PtrRead for [T; N] {}
+    unsafe impl<T: PtrRead> PtrRead for Wrapping<T> {}
+    unsafe impl<T: PtrRead> PtrRead for Option<T> {}
+    unsafe impl<T: PtrRead> PtrRead for Vec<T> {}
+    #[cfg(feature = "std")]
+    unsafe impl<T: PtrRead, V: PtrRead, S> PtrRead for HashMap<T, V, S> {}
+    unsafe impl<T: PtrRead, V: PtrRead> PtrRead for BTreeMap<T, V> {}
+    unsafe impl<T: PtrRead> PtrRead for BTreeSet<T> {}
+};
 
-pub struct DupIndexer<T, S = DefaultHashBuilder> {
+pub struct DupIndexer<T, S = DefaultHashBuilder, I = usize> {
     values: Vec<T>,
-    lookup: HashMap<ManuallyDrop<T>, usize, S>,
+    /// Indexes into `values`, hashed and compared using the values they point to.
+    lookup: HashTable<I>,
+    hasher: S,
+    /// The number of insertions of each value, if enabled with [`DupIndexer::enable_counts`].
+    counts: Option<Vec<usize>>,
+    /// The maximum number of values, set with [`DupIndexer::set_max_len`].
+    max_len: usize,
 }
 
-impl<T: PtrRead> DupIndexer<T> {
+impl<T> DupIndexer<T> {
     /// Create a new instance of `DupIndexer<T>`, without requiring `T` to implement `Default`.
     #[must_use]
     pub fn new() -> Self {
-        Self {
-            values: Vec::new(),
-            lookup: HashMap::with_hasher(DefaultHashBuilder::default()),
-        }
+        Self::with_hasher(DefaultHashBuilder::default())
     }
 
     /// Constructs a new, empty `DupIndexer<T>` with at least the specified capacity.
     #[must_use]
     pub fn with_capacity(capacity: usize) -> Self {
-        Self {
-            values: Vec::with_capacity(capacity),
-            lookup: HashMap::with_capacity_and_hasher(capacity, DefaultHashBuilder::default()),
-        }
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
     }
 }
 
-impl<T: PtrRead, S: BuildHasher> DupIndexer<T, S> {
+impl<T, S: BuildHasher, I: IndexType> DupIndexer<T, S, I> {
     /// Create a new instance of `DupIndexer<T>` using the provided hasher.
     #[must_use]
     pub fn with_hasher(hasher: S) -> Self {
         Self {
             values: Vec::new(),
-            lookup: HashMap::with_hasher(hasher),
+            lookup: HashTable::new(),
+            hasher,
+            counts: None,
+            max_len: usize::MAX,
         }
     }
 
@@ -91,7 +115,10 @@ impl<T: PtrRead, S: BuildHasher> DupIndexer<T, S> {
     pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
         Self {
             values: Vec::with_capacity(capacity),
-            lookup: HashMap::with_capacity_and_hasher(capacity, hasher),
+            lookup: HashTable::with_capacity(capacity),
+            hasher,
+            counts: None,
+            max_len: usize::MAX,
         }
     }
 
@@ -102,6 +129,20 @@ impl<T: PtrRead, S: BuildHasher> DupIndexer<T, S> {
         self.values.capacity()
     }
 
+    /// Returns the maximum number of values the indexer may hold, `usize::MAX` by default.
+    #[inline]
+    #[must_use]
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Set the maximum number of values the indexer may hold, e.g. to fit the limits of an output format.
+    /// Inserting any new value beyond this limit panics, while [`DupIndexer::try_insert`]
+    /// returns an error with the rejected value. The values that are already in the indexer are not removed.
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+    }
+
     /// Extracts a slice containing the entire indexer values.
     #[inline]
     #[must_use]
@@ -123,24 +164,75 @@ impl<T: PtrRead, S: BuildHasher> DupIndexer<T, S> {
         self.values.is_empty()
     }
 
+    /// Returns a reference to the value at the given index, or `None` if it is out of bounds.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, index: I) -> Option<&T> {
+        self.values.get(index.index())
+    }
+
     /// Converts the indexer into a vector.
     #[inline]
     #[must_use]
     pub fn into_vec(self) -> Vec<T> {
         self.values
     }
+
+    /// Start counting how many times each value was inserted, including the duplicate insertions.
+    /// The values inserted before this call are counted as inserted once.
+    /// Use [`DupIndexer::finish_by_frequency`] to get the values ordered by their counts.
+    pub fn enable_counts(&mut self) {
+        if self.counts.is_none() {
+            self.counts = Some(vec![1; self.values.len()]);
+        }
+    }
+
+    /// Returns the number of insertions of each value, or `None` if counting was not enabled
+    /// with [`DupIndexer::enable_counts`].
+    #[inline]
+    #[must_use]
+    pub fn counts(&self) -> Option<&[usize]> {
+        self.counts.as_deref()
+    }
+
+    /// Converts the indexer into a vector of values sorted by the descending number of insertions,
+    /// so that the most frequent values get the smallest indexes, e.g. for a compact varint encoding.
+    /// Values with the same count keep their insertion order.
+    /// Also returns a table that maps each old index to the new index of the value.
+    /// If counting was not enabled with [`DupIndexer::enable_counts`], the order is not changed.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexer;
+    /// # fn main() {
+    /// let mut di = DupIndexer::<&str>::new();
+    /// di.enable_counts();
+    /// di.insert("foo");
+    /// di.insert("bar");
+    /// di.insert("bar");
+    /// di.insert("baz");
+    /// di.insert("bar");
+    /// di.insert("baz");
+    /// let (values, remap) = di.finish_by_frequency();
+    /// assert_eq!(values, vec!["bar", "baz", "foo"]);
+    /// assert_eq!(remap, vec![2, 0, 1]);
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn finish_by_frequency(self) -> (Vec<T>, Vec<I>) {
+        sort_by_frequency(self.values, self.counts)
+    }
 }
 
 /// If `T` implements `Default`, create a new instance of `DupIndexer<T>`.
 /// Note that [`DupIndexer::new`] does not require `T` to implement `Default`.
-impl<T: PtrRead + Default> Default for DupIndexer<T> {
+impl<T: Default, I: IndexType> Default for DupIndexer<T, DefaultHashBuilder, I> {
     #[inline]
     fn default() -> Self {
-        Self::new()
+        Self::with_hasher(DefaultHashBuilder::default())
     }
 }
 
-impl<T: Eq + Hash, S: BuildHasher> DupIndexer<T, S> {
+impl<T: Eq + Hash, S: BuildHasher, I: IndexType> DupIndexer<T, S, I> {
     /// Insert a value into the indexer if it doesn't already exist,
     /// and return the index of the value.
     ///
@@ -154,43 +246,512 @@ impl<T: Eq + Hash, S: BuildHasher> DupIndexer<T, S> {
     /// assert_eq!(di.into_vec(), vec!["hello", "world"]);
     /// # }
     /// ```
-    pub fn insert(&mut self, value: T) -> usize {
-        // This is safe because we own the value and will not modify or drop it unless we consume the whole values vector,
-        // nor would we access the values in the vector before then.
-        // When dropping, index will be dropped without freeing the memory.
-        let dup_value = ManuallyDrop::new(unsafe { ptr::read(&value) });
-        match self.lookup.entry(dup_value) {
-            Occupied(entry) => *entry.get(),
-            Vacant(entry) => {
-                let index = self.values.len();
-                entry.insert(index);
-                self.values.push(value);
-                index
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert(&mut self, value: T) -> I {
+        self.insert_full(value).0
+    }
+
+    /// Insert a value into the indexer if it doesn't already exist,
+    /// and return the index of the value, and `true` if the value was newly inserted.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexer;
+    /// # fn main() {
+    /// let mut di = DupIndexer::<String>::new();
+    /// assert_eq!(di.insert_full("hello".to_string()), (0, true));
+    /// assert_eq!(di.insert_full("world".to_string()), (1, true));
+    /// assert_eq!(di.insert_full("hello".to_string()), (0, false));
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert_full(&mut self, value: T) -> (I, bool) {
+        self.entry(&value).insert_full(|| value)
+    }
+
+    /// Insert a clone of the value into the indexer if it doesn't already exist,
+    /// and return the index of the value. The value is only cloned if it is inserted.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexer;
+    /// # fn main() {
+    /// let mut di = DupIndexer::<Vec<u8>>::new();
+    /// let value = vec![1, 2, 3];
+    /// assert_eq!(di.insert_cloned(&value), 0);
+    /// assert_eq!(di.insert_cloned(&value), 0);
+    /// assert_eq!(di.insert_cloned(&vec![4]), 1);
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert_cloned(&mut self, value: &T) -> I
+    where
+        T: Clone,
+    {
+        self.entry(value).insert_full(|| value.clone()).0
+    }
+
+    /// Insert a value that is either borrowed or owned into the indexer if it doesn't already exist,
+    /// and return the index of the value. A borrowed value is only cloned if it is inserted,
+    /// and an owned value is moved into the indexer without cloning.
+    ///
+    /// ```
+    /// # use std::borrow::Cow;
+    /// # use dup_indexer::DupIndexer;
+    /// # fn main() {
+    /// let mut di = DupIndexer::<Vec<u8>>::new();
+    /// let value = vec![1, 2, 3];
+    /// assert_eq!(di.insert_cow(Cow::Borrowed(&value)), 0);
+    /// assert_eq!(di.insert_cow(Cow::Owned(vec![4])), 1);
+    /// assert_eq!(di.insert_cow(Cow::Owned(value)), 0);
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert_cow(&mut self, value: Cow<'_, T>) -> I
+    where
+        T: Clone,
+    {
+        match value {
+            Cow::Borrowed(value) => self.insert_cloned(value),
+            Cow::Owned(value) => self.insert(value),
+        }
+    }
+
+    /// Insert a value into the indexer if it doesn't already exist, and return the index of the value.
+    /// The value may be any borrowed form of `T`, e.g. `&str` for `DupIndexer<String>`,
+    /// and it is only converted into an owned value if it does not exist yet.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexer;
+    /// # fn main() {
+    /// let mut di = DupIndexer::<String>::new();
+    /// assert_eq!(di.insert_ref("hello"), 0);
+    /// assert_eq!(di.insert_ref("world"), 1);
+    /// assert_eq!(di.insert("hello".to_string()), 0);
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert_ref<Q>(&mut self, value: &Q) -> I
+    where
+        Q: Hash + Equivalent<T> + ToOwned<Owned = T> + ?Sized,
+    {
+        let hash = make_hash(&self.hasher, value);
+        self.insert_ref_with_hash(hash, value)
+    }
+
+    /// Insert a value into the indexer if it doesn't already exist, and return the index of the value,
+    /// or return an error with the rejected value if the indexer is full.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexer;
+    /// # fn main() {
+    /// let mut di = DupIndexer::<&str>::new();
+    /// di.set_max_len(1);
+    /// assert_eq!(di.try_insert("hello"), Ok(0));
+    /// assert_eq!(di.try_insert("hello"), Ok(0));
+    /// let err = di.try_insert("world").unwrap_err();
+    /// assert_eq!(err.into_value(), "world");
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`CapacityError`] if the number of values would exceed the maximum length
+    /// of the indexer, or the capacity of the index type `I`.
+    pub fn try_insert(&mut self, value: T) -> Result<I, CapacityError<T>> {
+        let (index, _) = self.entry(&value).try_insert_full(value, |value| value)?;
+        Ok(index)
+    }
+
+    /// Tries to reserve capacity for at least `additional` more values,
+    /// reporting an allocation failure instead of aborting.
+    ///
+    /// # Errors
+    /// Returns [`TryReserveError`] if the capacity overflows, or if the allocator reports a failure.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.values
+            .try_reserve(additional)
+            .map_err(TryReserveError::values)?;
+        if let Some(counts) = &mut self.counts {
+            counts
+                .try_reserve(additional)
+                .map_err(TryReserveError::values)?;
+        }
+        let values = &self.values;
+        let hasher = &self.hasher;
+        self.lookup
+            .try_reserve(additional, |&i| make_hash(hasher, &values[i.index()]))
+            .map_err(TryReserveError::lookup)
+    }
+
+    /// Get the entry of the given key for insertion or lookup. The key may be any borrowed
+    /// form of `T`, e.g. `&str` for `DupIndexer<String>`, or any other [`Equivalent`] type,
+    /// so that the owned value only needs to be created if it does not exist yet.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexer;
+    /// # fn main() {
+    /// let mut di = DupIndexer::<String>::new();
+    /// assert_eq!(di.entry("hello").or_insert_with(|| "hello".to_string()), 0);
+    /// assert_eq!(di.entry("world").or_insert_with(|| "world".to_string()), 1);
+    /// assert_eq!(di.entry("hello").or_insert_with(|| unreachable!()), 0);
+    /// assert_eq!(di.entry("foo").index(), None);
+    /// # }
+    /// ```
+    ///
+    /// Composite values can be queried without allocating by a type that hashes the same way,
+    /// and implements [`Equivalent`] to compare itself with the stored values:
+    ///
+    /// ```
+    /// # use dup_indexer::{DupIndexer, Equivalent};
+    /// #[derive(Hash)]
+    /// struct Key<'a>(&'a str, u32);
+    ///
+    /// impl Equivalent<(String, u32)> for Key<'_> {
+    ///     fn equivalent(&self, value: &(String, u32)) -> bool {
+    ///         self.0 == value.0 && self.1 == value.1
+    ///     }
+    /// }
+    ///
+    /// # fn main() {
+    /// let mut di = DupIndexer::<(String, u32)>::new();
+    /// di.insert(("foo".to_string(), 1));
+    /// let key = Key("bar", 2);
+    /// assert_eq!(di.entry(&key).or_insert_with(|| (key.0.to_string(), key.1)), 1);
+    /// assert_eq!(di.get_index_of(&Key("foo", 1)), Some(0));
+    /// assert_eq!(di.get_index_of(&Key("foo", 2)), None);
+    /// # }
+    /// ```
+    pub fn entry<Q>(&mut self, key: &Q) -> Entry<'_, T, I>
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+    {
+        let hash = make_hash(&self.hasher, key);
+        self.entry_with_hash(hash, key)
+    }
+
+    fn entry_with_hash<Q>(&mut self, hash: u64, key: &Q) -> Entry<'_, T, I>
+    where
+        Q: Equivalent<T> + ?Sized,
+    {
+        let values = &self.values;
+        let hasher = &self.hasher;
+        let entry = self.lookup.entry(
+            hash,
+            |&i| key.equivalent(&values[i.index()]),
+            |&i| make_hash(hasher, &values[i.index()]),
+        );
+        Entry::new(entry, &mut self.values, &mut self.counts, self.max_len)
+    }
+
+    /// Compute the hash of a value with the hasher of this indexer. The hash can be computed once,
+    /// e.g. to pick a shard, and then reused with [`DupIndexer::insert_with_hash`],
+    /// [`DupIndexer::insert_ref_with_hash`], or [`DupIndexer::get_index_of_with_hash`].
+    /// The value may be any borrowed form of `T` or any other [`Equivalent`] type that hashes
+    /// the same way, e.g. `&str` for `String`.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexer;
+    /// # fn main() {
+    /// let mut di = DupIndexer::<String>::new();
+    /// let hash = di.hash_of("hello");
+    /// assert_eq!(di.insert_ref_with_hash(hash, "hello"), 0);
+    /// assert_eq!(di.insert_with_hash(hash, "hello".to_string()), 0);
+    /// assert_eq!(di.get_index_of_with_hash(hash, "hello"), Some(0));
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn hash_of<Q: Hash + ?Sized>(&self, value: &Q) -> u64 {
+        make_hash(&self.hasher, value)
+    }
+
+    /// Same as [`DupIndexer::insert`], but uses a hash computed with [`DupIndexer::hash_of`]
+    /// instead of hashing the value again. If the hash does not match the value,
+    /// the indexer may store duplicate values, or fail to find them later.
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert_with_hash(&mut self, hash: u64, value: T) -> I {
+        self.entry_with_hash(hash, &value).insert_full(|| value).0
+    }
+
+    /// Same as [`DupIndexer::insert_ref`], but uses a hash computed with [`DupIndexer::hash_of`]
+    /// instead of hashing the value again. If the hash does not match the value,
+    /// the indexer may store duplicate values, or fail to find them later.
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn insert_ref_with_hash<Q>(&mut self, hash: u64, value: &Q) -> I
+    where
+        Q: Equivalent<T> + ToOwned<Owned = T> + ?Sized,
+    {
+        self.entry_with_hash(hash, value)
+            .insert_full(|| value.to_owned())
+            .0
+    }
+
+    /// Same as [`DupIndexer::get_index_of`], but uses a hash computed with [`DupIndexer::hash_of`]
+    /// instead of hashing the value again.
+    #[must_use]
+    pub fn get_index_of_with_hash<Q>(&self, hash: u64, value: &Q) -> Option<I>
+    where
+        Q: Equivalent<T> + ?Sized,
+    {
+        self.lookup
+            .find(hash, |&i| value.equivalent(&self.values[i.index()]))
+            .copied()
+    }
+
+    /// Retain only the values for which the predicate returns `true`, preserving their order.
+    /// Returns a table that maps each old index to the new index of the value,
+    /// or to `None` if the value was removed.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexer;
+    /// # fn main() {
+    /// let mut di = DupIndexer::<&str>::new();
+    /// di.insert("foo");
+    /// di.insert("bar");
+    /// di.insert("baz");
+    /// assert_eq!(di.retain(|v| v.starts_with('b')), vec![None, Some(0), Some(1)]);
+    /// assert_eq!(di.insert("baz"), 1);
+    /// assert_eq!(di.into_vec(), vec!["bar", "baz"]);
+    /// # }
+    /// ```
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut keep: F) -> Vec<Option<I>> {
+        let remap: Vec<Option<I>> = compact_remap(self.values.iter().map(&mut keep));
+        self.lookup.retain(|index| match remap[index.index()] {
+            Some(new_index) => {
+                *index = new_index;
+                true
             }
+            None => false,
+        });
+        let mut old_index = 0;
+        self.values.retain(|_| {
+            old_index += 1;
+            remap[old_index - 1].is_some()
+        });
+        if let Some(counts) = &mut self.counts {
+            let mut old_index = 0;
+            counts.retain(|_| {
+                old_index += 1;
+                remap[old_index - 1].is_some()
+            });
+        }
+        remap
+    }
+
+    /// Remove the values at the given indexes, preserving the order of the remaining values.
+    /// Returns a table that maps each old index to the new index of the value,
+    /// or to `None` if the value was removed.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexer;
+    /// # fn main() {
+    /// let mut di = DupIndexer::<&str>::new();
+    /// di.insert("foo");
+    /// di.insert("bar");
+    /// di.insert("baz");
+    /// assert_eq!(di.remove_many([0, 2]), vec![None, Some(0), None]);
+    /// assert_eq!(di.into_vec(), vec!["bar"]);
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if any of the indexes is out of bounds.
+    pub fn remove_many<It: IntoIterator<Item = I>>(&mut self, indexes: It) -> Vec<Option<I>> {
+        let mut removed = vec![false; self.values.len()];
+        for index in indexes {
+            removed[index.index()] = true;
+        }
+        let mut removed = removed.into_iter();
+        self.retain(|_| removed.next() == Some(false))
+    }
+
+    /// Move all values of another indexer into this one, keeping the indexes of existing values.
+    /// Values that already exist in this indexer are dropped, and the new ones are appended in
+    /// the index order of `other`, without cloning. If counting is enabled for this indexer,
+    /// the counts of `other` are added to it, or 1 per value if `other` did not count insertions.
+    /// Returns a table that maps each index of `other` to the index of the value in this indexer.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexer;
+    /// # fn main() {
+    /// let mut di = DupIndexer::<String>::new();
+    /// di.insert("foo".to_string());
+    /// di.insert("bar".to_string());
+    /// let mut other = DupIndexer::<String>::new();
+    /// other.insert("baz".to_string());
+    /// other.insert("foo".to_string());
+    /// assert_eq!(di.merge(other), vec![2, 0]);
+    /// assert_eq!(di.into_vec(), vec!["foo", "bar", "baz"]);
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn merge<S2, I2>(&mut self, other: DupIndexer<T, S2, I2>) -> Vec<I> {
+        let mut counts = other.counts.map(Vec::into_iter);
+        other
+            .values
+            .into_iter()
+            .map(|value| {
+                let count = counts.as_mut().and_then(Iterator::next).unwrap_or(1);
+                let index = self.insert_full(value).0;
+                if let Some(counts) = &mut self.counts {
+                    // `insert_full` has already counted one insertion
+                    counts[index.index()] += count - 1;
+                }
+                index
+            })
+            .collect()
+    }
+
+    /// Insert all values of the iterator into the indexer, and return the index of each value
+    /// in the order of the iterator. This dictionary-encodes a sequence in a single pass:
+    /// the indexer keeps the unique values, and the returned vector keeps the code of each item.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexer;
+    /// # fn main() {
+    /// let mut di = DupIndexer::new();
+    /// let codes = di.extend_indices(["a", "b", "a", "c", "b"]);
+    /// assert_eq!(codes, vec![0, 1, 0, 2, 1]);
+    /// assert_eq!(di.into_vec(), vec!["a", "b", "c"]);
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the number of values exceeds the maximum length of the indexer,
+    /// or the capacity of the index type `I`.
+    pub fn extend_indices<It: IntoIterator<Item = T>>(&mut self, iter: It) -> Vec<I> {
+        let iter = iter.into_iter();
+        let size_hint = iter.size_hint().0;
+        self.reserve_for(size_hint);
+        let mut indexes = Vec::with_capacity(size_hint);
+        indexes.extend(iter.map(|value| self.insert(value)));
+        indexes
+    }
+
+    /// Reserve capacity for an iterator with the given lower size bound. Some of its values are
+    /// likely to be duplicates if the indexer is not empty, so only half of them are reserved.
+    fn reserve_for(&mut self, size_hint: usize) {
+        let additional = if self.values.is_empty() {
+            size_hint
+        } else {
+            size_hint - size_hint / 2
+        };
+        let additional = additional.min(self.max_len.saturating_sub(self.values.len()));
+        self.values.reserve(additional);
+        if let Some(counts) = &mut self.counts {
+            counts.reserve(additional);
+        }
+        let values = &self.values;
+        let hasher = &self.hasher;
+        self.lookup
+            .reserve(additional, |&i| make_hash(hasher, &values[i.index()]));
+    }
+
+    /// Converts the indexer into a read-only [`FrozenIndexer`] that uses a minimal perfect hash
+    /// function instead of a hash table, keeping the same indexes of all values.
+    ///
+    /// # Panics
+    /// Panics if the perfect hash function cannot be built with any of the tried hash seeds.
+    /// Values with identical hashes are handled separately, so this is extremely unlikely.
+    #[must_use]
+    pub fn freeze(self) -> FrozenIndexer<T, S, I> {
+        FrozenIndexer::new(self.values, self.hasher)
+    }
+
+    /// Return the index of the value if it exists in the indexer, without inserting it.
+    /// The value may be any borrowed form of `T`, e.g. `&str` for `DupIndexer<String>`,
+    /// or any other [`Equivalent`] type.
+    ///
+    /// ```
+    /// # use dup_indexer::DupIndexer;
+    /// # fn main() {
+    /// let mut di = DupIndexer::<String>::new();
+    /// di.insert("hello".to_string());
+    /// di.insert("world".to_string());
+    /// assert_eq!(di.get_index_of("world"), Some(1));
+    /// assert_eq!(di.get_index_of("foo"), None);
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn get_index_of<Q>(&self, value: &Q) -> Option<I>
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+    {
+        self.get_index_of_with_hash(make_hash(&self.hasher, value), value)
+    }
+
+    /// Return true if the value exists in the indexer.
+    /// The value may be any borrowed form of `T`, e.g. `&str` for `DupIndexer<String>`,
+    /// or any other [`Equivalent`] type.
+    #[inline]
+    #[must_use]
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+    {
+        self.get_index_of(value).is_some()
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher + Default, I: IndexType> FromIterator<T> for DupIndexer<T, S, I> {
+    fn from_iter<It: IntoIterator<Item = T>>(iter: It) -> Self {
+        let mut di = Self::with_hasher(S::default());
+        di.extend(iter);
+        di
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher, I: IndexType> Extend<T> for DupIndexer<T, S, I> {
+    fn extend<It: IntoIterator<Item = T>>(&mut self, iter: It) {
+        let iter = iter.into_iter();
+        self.reserve_for(iter.size_hint().0);
+        for value in iter {
+            self.insert(value);
         }
     }
 }
 
-impl<T, S> Index<usize> for DupIndexer<T, S> {
+impl<T, S, I: IndexType> Index<I> for DupIndexer<T, S, I> {
     type Output = T;
 
     #[inline]
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.values[index]
+    fn index(&self, index: I) -> &Self::Output {
+        &self.values[index.index()]
     }
 }
 
-impl<T, S> IntoIterator for DupIndexer<T, S> {
+impl<T, S, I> IntoIterator for DupIndexer<T, S, I> {
     type Item = T;
-    type IntoIter = std::vec::IntoIter<T>;
+    type IntoIter = vec::IntoIter<T>;
 
     #[inline]
-    fn into_iter(self) -> std::vec::IntoIter<T> {
+    fn into_iter(self) -> vec::IntoIter<T> {
         self.values.into_iter()
     }
 }
 
-impl<T, S> Deref for DupIndexer<T, S> {
+impl<T, S, I> Deref for DupIndexer<T, S, I> {
     type Target = [T];
 
     #[inline]
@@ -199,8 +760,8 @@ impl<T, S> Deref for DupIndexer<T, S> {
     }
 }
 
-impl<T: Debug, S> Debug for DupIndexer<T, S> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+impl<T: Debug, S, I> Debug for DupIndexer<T, S, I> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         f.debug_map()
             .entries(self.values.iter().enumerate())
             .finish()
@@ -210,6 +771,7 @@ impl<T: Debug, S> Debug for DupIndexer<T, S> {
 #[cfg(test)]
 mod tests {
     use std::collections::hash_map::RandomState;
+    use std::rc::Rc;
 
     use super::*;
 
@@ -250,6 +812,298 @@ mod tests {
         assert_eq!(di.into_vec(), vec!["foo", "bar"]);
     }
 
+    #[test]
+    fn test_insert_full() {
+        let mut di: DupIndexer<&str> = DupIndexer::new();
+        assert_eq!(di.insert_full("foo"), (0, true));
+        assert_eq!(di.insert_full("bar"), (1, true));
+        assert_eq!(di.insert_full("foo"), (0, false));
+        assert_eq!(di.insert("bar"), 1);
+        assert_eq!(di.insert_full("bar"), (1, false));
+        assert_eq!(di.into_vec(), vec!["foo", "bar"]);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut di: DupIndexer<String> = DupIndexer::new();
+        for value in ["a", "bb", "c", "dd", "e"] {
+            di.insert(value.to_string());
+        }
+        let remap = di.retain(|v| v.len() == 1);
+        assert_eq!(remap, vec![Some(0), None, Some(1), None, Some(2)]);
+        assert_eq!(di.as_slice(), &["a", "c", "e"]);
+        assert_eq!(di.get_index_of("e"), Some(2));
+        assert_eq!(di.get_index_of("bb"), None);
+        assert_eq!(di.insert("bb".to_string()), 3);
+        assert_eq!(di.insert("c".to_string()), 1);
+
+        assert_eq!(di.retain(|_| false), vec![None; 4]);
+        assert!(di.is_empty());
+        assert_eq!(di.insert("a".to_string()), 0);
+    }
+
+    #[test]
+    fn test_remove_many() {
+        let mut di: DupIndexer<i32, DefaultHashBuilder, u32> = DupIndexer::default();
+        for value in 10..15 {
+            di.insert(value);
+        }
+        let remap = di.remove_many([3, 1, 3]);
+        assert_eq!(remap, vec![Some(0), None, Some(1), None, Some(2)]);
+        assert_eq!(di.as_slice(), &[10, 12, 14]);
+        assert_eq!(di.get_index_of(&14), Some(2));
+        assert_eq!(di.insert(11), 3);
+        assert_eq!(di.remove_many([]), vec![Some(0), Some(1), Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut di: DupIndexer<String, DefaultHashBuilder, u16> = DupIndexer::default();
+        di.insert("a".to_string());
+        di.insert("b".to_string());
+        di.enable_counts();
+        let mut other: DupIndexer<String, RandomState, u8> =
+            DupIndexer::with_hasher(RandomState::new());
+        for value in ["c", "b", "d", "c"] {
+            other.insert(value.to_string());
+        }
+        other.enable_counts();
+        other.insert("c".to_string());
+        assert_eq!(di.merge(other), vec![2, 1, 3]);
+        assert_eq!(di.as_slice(), &["a", "b", "c", "d"]);
+        assert_eq!(di.counts(), Some([1, 2, 2, 1].as_slice()));
+        assert_eq!(di.get_index_of("d"), Some(3));
+        assert!(di.merge(DupIndexer::<String>::new()).is_empty());
+        assert_eq!(di.len(), 4);
+    }
+
+    #[test]
+    fn test_from_iter_and_extend() {
+        let mut di: DupIndexer<i32, RandomState, u8> = [3, 1, 3, 2].into_iter().collect();
+        assert_eq!(di.as_slice(), &[3, 1, 2]);
+        assert!(di.capacity() >= 4);
+        di.enable_counts();
+        di.extend([2, 4, 2]);
+        assert_eq!(di.as_slice(), &[3, 1, 2, 4]);
+        assert_eq!(di.counts(), Some([1, 1, 3, 1].as_slice()));
+        assert_eq!(di.extend_indices(vec![4, 5, 3]), vec![3, 4, 0]);
+        assert!(di.extend_indices(Vec::new()).is_empty());
+        assert_eq!(di.len(), 5);
+    }
+
+    #[test]
+    fn test_with_hash() {
+        let mut di: DupIndexer<Vec<u32>, RandomState, u8> =
+            DupIndexer::with_hasher(RandomState::new());
+        di.enable_counts();
+        let hash = di.hash_of([1, 2, 3].as_slice());
+        assert_eq!(hash, di.hash_of(&vec![1, 2, 3]));
+        assert_eq!(di.get_index_of_with_hash(hash, [1, 2, 3].as_slice()), None);
+        assert_eq!(di.insert_ref_with_hash(hash, [1, 2, 3].as_slice()), 0);
+        assert_eq!(di.insert_with_hash(hash, vec![1, 2, 3]), 0);
+        assert_eq!(di.insert(vec![4]), 1);
+        let hash = di.hash_of(&vec![4]);
+        assert_eq!(di.get_index_of_with_hash(hash, &vec![4]), Some(1));
+        assert_eq!(di.insert_with_hash(hash, vec![4]), 1);
+        assert_eq!(di.counts(), Some([2, 2].as_slice()));
+    }
+
+    #[test]
+    fn test_equivalent() {
+        #[derive(Hash)]
+        struct Path<'a>(&'a [&'a str]);
+
+        impl Equivalent<Vec<String>> for Path<'_> {
+            fn equivalent(&self, value: &Vec<String>) -> bool {
+                self.0.len() == value.len() && self.0.iter().zip(value).all(|(a, b)| a == b)
+            }
+        }
+
+        let mut di: DupIndexer<Vec<String>> = DupIndexer::new();
+        di.insert(vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(di.get_index_of(&Path(&["a", "b"])), Some(0));
+        assert_eq!(di.get_index_of(&Path(&["a"])), None);
+        assert!(di.contains(&Path(&["a", "b"])));
+        let key = Path(&["c"]);
+        let index = di
+            .entry(&key)
+            .or_insert_with(|| key.0.iter().map(|v| (*v).to_string()).collect());
+        assert_eq!(index, 1);
+        assert_eq!(di.entry(&Path(&["c"])).index(), Some(1));
+        let hash = di.hash_of(&Path(&["a", "b"]));
+        assert_eq!(di.get_index_of_with_hash(hash, &Path(&["a", "b"])), Some(0));
+        assert_eq!(di.len(), 2);
+    }
+
+    #[test]
+    fn test_insert_cloned() {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        enum Value {
+            Int(i64),
+            List(Vec<String>),
+        }
+
+        let mut di: DupIndexer<Value, RandomState, u8> =
+            DupIndexer::with_hasher(RandomState::new());
+        di.enable_counts();
+        let list = Value::List(vec!["a".to_string()]);
+        assert_eq!(di.insert_cloned(&list), 0);
+        assert_eq!(di.insert_cloned(&Value::Int(1)), 1);
+        assert_eq!(di.insert_cloned(&list), 0);
+        assert_eq!(di.insert_cow(Cow::Borrowed(&Value::Int(1))), 1);
+        assert_eq!(di.insert_cow(Cow::Owned(Value::Int(2))), 2);
+        assert_eq!(di.insert_cow(Cow::Owned(list)), 0);
+        assert_eq!(di.counts(), Some([3, 2, 1].as_slice()));
+    }
+
+    #[test]
+    fn test_insert_ref() {
+        let mut di: DupIndexer<Vec<u8>> = DupIndexer::new();
+        di.enable_counts();
+        assert_eq!(di.insert_ref([1, 2].as_slice()), 0);
+        assert_eq!(di.insert_ref([3].as_slice()), 1);
+        assert_eq!(di.insert(vec![1, 2]), 0);
+        assert_eq!(di.insert_ref([1, 2].as_slice()), 0);
+        assert_eq!(di.counts(), Some([3, 1].as_slice()));
+        assert_eq!(di.into_vec(), vec![vec![1, 2], vec![3]]);
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut di: DupIndexer<String, DefaultHashBuilder, u8> = DupIndexer::default();
+        di.enable_counts();
+        assert_eq!(di.entry("foo").or_insert("foo".to_string()), 0);
+        assert_eq!(di.entry("foo").or_insert_with(|| unreachable!()), 0);
+        match di.entry("bar") {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(entry) => assert_eq!(entry.insert("bar".to_string()), 1),
+        }
+        match di.entry("foo") {
+            Entry::Occupied(entry) => {
+                assert_eq!(entry.index(), 0);
+                assert_eq!(entry.get(), "foo");
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(di.entry("bar").index(), Some(1));
+        assert_eq!(di.entry("baz").index(), None);
+        assert_eq!(di.counts(), Some([2, 1].as_slice()));
+        assert_eq!(di.into_vec(), vec!["foo", "bar"]);
+    }
+
+    #[test]
+    fn test_max_len() {
+        let mut di: DupIndexer<i32, DefaultHashBuilder, u8> = DupIndexer::default();
+        assert_eq!(di.max_len(), usize::MAX);
+        di.set_max_len(2);
+        assert_eq!(di.try_insert(10), Ok(0));
+        assert_eq!(di.try_insert(20), Ok(1));
+        assert_eq!(di.try_insert(10), Ok(0));
+        assert_eq!(
+            di.try_insert(30).map_err(CapacityError::into_value),
+            Err(30)
+        );
+        assert_eq!(di.insert(20), 1);
+        di.set_max_len(300);
+        for value in 2..256 {
+            assert_eq!(
+                di.try_insert(value * 1000),
+                Ok(u8::try_from(value).unwrap())
+            );
+        }
+        let err = di.try_insert(-1).unwrap_err();
+        assert_eq!(err.value(), &-1);
+        assert_eq!(
+            err.to_string(),
+            "the indexer is full, the value cannot be inserted"
+        );
+        assert_eq!(di.len(), 256);
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the maximum length of the indexer")]
+    fn test_max_len_overflow() {
+        let mut di: DupIndexer<i32> = DupIndexer::new();
+        di.set_max_len(1);
+        di.insert(1);
+        di.insert(2);
+    }
+
+    #[test]
+    fn test_try_reserve() {
+        let mut di: DupIndexer<String> = DupIndexer::new();
+        di.enable_counts();
+        assert!(di.try_reserve(100).is_ok());
+        assert!(di.capacity() >= 100);
+        assert!(di.try_reserve(usize::MAX).is_err());
+        assert_eq!(di.insert("foo".to_string()), 0);
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut di: DupIndexer<&str> = DupIndexer::new();
+        assert_eq!(di.insert("a"), 0);
+        assert_eq!(di.counts(), None);
+        di.enable_counts();
+        assert_eq!(di.counts(), Some([1].as_slice()));
+        assert_eq!(di.insert("b"), 1);
+        assert_eq!(di.insert("c"), 2);
+        assert_eq!(di.insert("c"), 2);
+        assert_eq!(di.insert("d"), 3);
+        assert_eq!(di.insert("d"), 3);
+        assert_eq!(di.insert("d"), 3);
+        assert_eq!(di.counts(), Some([1, 1, 2, 3].as_slice()));
+        assert_eq!(di.remove_many([1]), vec![Some(0), None, Some(1), Some(2)]);
+        assert_eq!(di.counts(), Some([1, 2, 3].as_slice()));
+        let (values, remap) = di.finish_by_frequency();
+        assert_eq!(values, vec!["d", "c", "a"]);
+        assert_eq!(remap, vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut di: DupIndexer<String> = DupIndexer::new();
+        assert_eq!(di.get_index_of("foo"), None);
+        assert!(!di.contains("foo"));
+        assert_eq!(di.get(0), None);
+        assert_eq!(di.insert("foo".to_string()), 0);
+        assert_eq!(di.insert("bar".to_string()), 1);
+        assert_eq!(di.get_index_of("foo"), Some(0));
+        assert_eq!(di.get_index_of(&"bar".to_string()), Some(1));
+        assert_eq!(di.get_index_of("baz"), None);
+        assert!(di.contains("bar"));
+        assert!(!di.contains("baz"));
+        assert_eq!(di.get(1).map(String::as_str), Some("bar"));
+        assert_eq!(di.get(2), None);
+        assert_eq!(di.len(), 2);
+
+        let mut di: DupIndexer<Vec<u8>> = DupIndexer::new();
+        assert_eq!(di.insert(vec![1, 2, 3]), 0);
+        assert_eq!(di.get_index_of([1, 2, 3].as_slice()), Some(0));
+        assert!(!di.contains([1, 2].as_slice()));
+    }
+
+    #[test]
+    fn test_index_type() {
+        let mut di: DupIndexer<String, DefaultHashBuilder, u32> = DupIndexer::default();
+        assert_eq!(di.insert("foo".to_string()), 0_u32);
+        assert_eq!(di.insert("bar".to_string()), 1_u32);
+        assert_eq!(di.insert("foo".to_string()), 0_u32);
+        assert_eq!(di.get_index_of("bar"), Some(1_u32));
+        assert_eq!(di[1_u32], "bar");
+        assert_eq!(di.get(0_u32).map(String::as_str), Some("foo"));
+        assert_eq!(di.into_vec(), vec!["foo", "bar"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the capacity of the index type")]
+    fn test_index_type_overflow() {
+        let mut di: DupIndexer<u16, DefaultHashBuilder, u8> = DupIndexer::default();
+        for value in 0..=256 {
+            di.insert(value);
+        }
+    }
+
     #[test]
     fn test_custom_hasher() {
         let mut di: DupIndexer<String, RandomState> = DupIndexer::with_hasher(RandomState::new());
@@ -281,8 +1135,6 @@ mod tests {
         #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
         struct Foo(pub i32);
 
-        unsafe impl PtrRead for Foo {}
-
         let mut di: DupIndexer<Foo> = DupIndexer::new();
         assert_eq!(di.insert(Foo(42)), 0);
         assert_eq!(di.insert(Foo(13)), 1);
@@ -349,8 +1201,6 @@ mod tests {
             Int(i32),
         }
 
-        unsafe impl PtrRead for Value {}
-
         let mut di: DupIndexer<Value> = DupIndexer::new();
         assert_eq!(di.insert(Value::Str("foo".to_string())), 0);
         assert_eq!(di.insert(Value::Int(42)), 1);
@@ -362,16 +1212,27 @@ mod tests {
         );
     }
 
-    // // This test is ignored on Miri because it fails without any good explanation at the moment.
-    // // See issue https://github.com/nyurik/dup-indexer/issues/1
-    // #[test]
-    // #[cfg_attr(miri, ignore)]
-    // fn test_box() {
-    //     let mut di: DupIndexer<Box<i32>> = DupIndexer::default();
-    //     assert_eq!(di.insert(Box::new(42)), 0);
-    //     assert_eq!(di.insert(Box::new(13)), 1);
-    //     assert_eq!(di.insert(Box::new(42)), 0);
-    //     assert_eq!(di[1], Box::new(13));
-    //     assert_eq!(di.into_vec(), vec![Box::new(42), Box::new(13)]);
-    // }
+    #[test]
+    fn test_box() {
+        let mut di: DupIndexer<Box<i32>> = DupIndexer::default();
+        assert_eq!(di.insert(Box::new(42)), 0);
+        assert_eq!(di.insert(Box::new(13)), 1);
+        assert_eq!(di.insert(Box::new(42)), 0);
+        assert_eq!(di[1], Box::new(13));
+        assert_eq!(di.get_index_of(&13), Some(1));
+        assert_eq!(di.into_vec(), vec![Box::new(42), Box::new(13)]);
+    }
+
+    #[test]
+    fn test_rc() {
+        let mut di: DupIndexer<Rc<str>> = DupIndexer::new();
+        let foo: Rc<str> = Rc::from("foo");
+        assert_eq!(di.insert(Rc::clone(&foo)), 0);
+        assert_eq!(di.insert(Rc::from("bar")), 1);
+        assert_eq!(di.insert(Rc::from("foo")), 0);
+        assert_eq!(di.get_index_of("bar"), Some(1));
+        assert_eq!(Rc::strong_count(&foo), 2);
+        drop(di);
+        assert_eq!(Rc::strong_count(&foo), 1);
+    }
 }
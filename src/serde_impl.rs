@@ -0,0 +1,183 @@
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+use core::ops::Deref;
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{DupIndexer, DupIndexerRefs, FromTarget, IndexType, TotalF32, TotalF64};
+
+/// Serializes the indexer as a sequence of its values, in the index order.
+/// The insertion counts and the `max_len` limit are not serialized.
+impl<T: Serialize, S, I> Serialize for DupIndexer<T, S, I> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+/// Deserializes the indexer from a sequence of unique values, rebuilding the lookup table.
+/// Returns an error if the sequence contains duplicate values,
+/// or if it has more values than the index type `I` can address.
+/// The resulting indexer does not count insertions and has no `max_len` limit,
+/// use `enable_counts` and `set_max_len` to restore them if needed.
+impl<'de, T, S, I> Deserialize<'de> for DupIndexer<T, S, I>
+where
+    T: Deserialize<'de> + Eq + Hash,
+    S: BuildHasher + Default,
+    I: IndexType,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = Vec::<T>::deserialize(deserializer)?;
+        check_index_type::<I, D::Error>(values.len())?;
+        let mut di = Self::with_capacity_and_hasher(values.len(), S::default());
+        for (index, value) in values.into_iter().enumerate() {
+            if !di.insert_full(value).1 {
+                return Err(duplicate_error(index));
+            }
+        }
+        Ok(di)
+    }
+}
+
+/// Serializes the indexer as a sequence of its values, in the index order.
+/// The insertion counts and the `max_len` limit are not serialized.
+impl<T, S, I> Serialize for DupIndexerRefs<T, S, I>
+where
+    T: Serialize,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+/// Deserializes the indexer from a sequence of unique values, rebuilding the lookup table.
+/// Returns an error if the sequence contains duplicate values,
+/// or if it has more values than the index type `I` can address.
+/// The resulting indexer does not count insertions and has no `max_len` limit,
+/// use `enable_counts` and `set_max_len` to restore them if needed.
+impl<'de, T, S, I> Deserialize<'de> for DupIndexerRefs<T, S, I>
+where
+    T: FromTarget + Eq + Hash + Deserialize<'de>,
+    <T as Deref>::Target: Eq + Hash,
+    S: BuildHasher + Default,
+    I: IndexType,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = Vec::<T>::deserialize(deserializer)?;
+        check_index_type::<I, D::Error>(values.len())?;
+        let mut di = Self::with_capacity_and_hasher(values.len(), S::default());
+        for (index, value) in values.into_iter().enumerate() {
+            if !di.insert_owned_full(value).1 {
+                return Err(duplicate_error(index));
+            }
+        }
+        Ok(di)
+    }
+}
+
+macro_rules! total_float_serde {
+    ($name:ident, $float:ty) => {
+        /// Serializes the wrapped float value as is.
+        impl Serialize for $name {
+            fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+                self.get().serialize(serializer)
+            }
+        }
+
+        /// Deserializes a float value without changing it.
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <$float>::deserialize(deserializer).map(Self::new)
+            }
+        }
+    };
+}
+
+total_float_serde!(TotalF32, f32);
+total_float_serde!(TotalF64, f64);
+
+fn check_index_type<I: IndexType, E: Error>(len: usize) -> Result<(), E> {
+    if len > 0 && I::try_from_usize(len - 1).is_none() {
+        return Err(E::custom(format_args!(
+            "{len} values exceed the capacity of the index type"
+        )));
+    }
+    Ok(())
+}
+
+fn duplicate_error<E: Error>(index: usize) -> E {
+    E::custom(format_args!("duplicate value at index {index}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DefaultHashBuilder, DupIndexer, DupIndexerRefs, TotalF64};
+
+    #[test]
+    fn test_round_trip() {
+        let mut di: DupIndexer<String> = DupIndexer::new();
+        di.insert("foo".to_string());
+        di.insert("bar".to_string());
+        let json = serde_json::to_string(&di).unwrap();
+        assert_eq!(json, r#"["foo","bar"]"#);
+
+        let mut di: DupIndexer<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(di.get_index_of("bar"), Some(1));
+        assert_eq!(di.insert("foo".to_string()), 0);
+        assert_eq!(di.insert("baz".to_string()), 2);
+
+        let mut di: DupIndexerRefs<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(di.insert_ref("bar"), 1);
+        assert_eq!(di.insert_ref("baz"), 2);
+        assert_eq!(
+            serde_json::to_string(&di).unwrap(),
+            r#"["foo","bar","baz"]"#
+        );
+    }
+
+    #[test]
+    fn test_settings_not_serialized() {
+        let mut di: DupIndexer<u32> = DupIndexer::new();
+        di.enable_counts();
+        di.set_max_len(2);
+        di.insert(1);
+        di.insert(1);
+        let json = serde_json::to_string(&di).unwrap();
+        assert_eq!(json, "[1]");
+        let di: DupIndexer<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(di.counts(), None);
+        assert_eq!(di.max_len(), usize::MAX);
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let json = r#"["foo","bar","foo"]"#;
+        let err = serde_json::from_str::<DupIndexer<String>>(json).unwrap_err();
+        assert!(err.to_string().contains("duplicate value at index 2"));
+        let err = serde_json::from_str::<DupIndexerRefs<String>>(json).unwrap_err();
+        assert!(err.to_string().contains("duplicate value at index 2"));
+    }
+
+    #[test]
+    fn test_index_type_capacity() {
+        let json = serde_json::to_string(&(0..300).collect::<Vec<u32>>()).unwrap();
+        let err =
+            serde_json::from_str::<DupIndexer<u32, DefaultHashBuilder, u8>>(&json).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("exceed the capacity of the index type"));
+        let di: DupIndexer<u32, DefaultHashBuilder, u16> = serde_json::from_str(&json).unwrap();
+        assert_eq!(di.len(), 300);
+    }
+
+    #[test]
+    fn test_total_float() {
+        let mut di: DupIndexer<TotalF64> = DupIndexer::new();
+        di.insert(TotalF64::new(1.5));
+        di.insert(TotalF64::new(-0.0));
+        let json = serde_json::to_string(&di).unwrap();
+        assert_eq!(json, "[1.5,-0.0]");
+        let di: DupIndexer<TotalF64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(di.get_index_of(&TotalF64::new(-0.0)), Some(1));
+    }
+}
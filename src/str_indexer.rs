@@ -0,0 +1,389 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Formatter};
+use core::hash::BuildHasher;
+use core::ops::Index;
+
+use hashbrown::hash_table::Entry::{Occupied, Vacant};
+use hashbrown::HashTable;
+
+use crate::index::next_index;
+use crate::{make_hash, DefaultHashBuilder, IndexType};
+
+/// The minimum capacity of each chunk of the string arena, in bytes.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A string indexer that stores all unique strings in a few large chunks of memory,
+/// instead of allocating a separate `String` for each value like `DupIndexerRefs<String>`.
+///
+/// Each inserted string is appended to the current chunk, and is available as a `&str` view into it.
+/// A full chunk is never reallocated. Instead, a new chunk is started, so the strings are never copied,
+/// and growing the arena does not need twice its memory. Strings larger than a chunk get a chunk
+/// of their own, without interrupting the current one. When done, the strings and the offsets
+/// of each string can be exported as a single string table with [`DupStrIndexer::into_parts`].
+///
+/// ```
+/// # use dup_indexer::DupStrIndexer;
+/// # fn main() {
+/// let mut di = DupStrIndexer::new();
+/// assert_eq!(di.insert_ref("hello"), 0);
+/// assert_eq!(di.insert_ref("world"), 1);
+/// assert_eq!(di.insert_ref("hello"), 0);
+/// assert_eq!(&di[1], "world");
+/// let (bytes, offsets) = di.into_parts();
+/// assert_eq!(bytes, "helloworld");
+/// assert_eq!(offsets, vec![0, 5, 10]);
+/// # }
+/// ```
+pub struct DupStrIndexer<S = DefaultHashBuilder, I = usize> {
+    arena: Arena,
+    /// Indexes into `offsets`, hashed and compared using the strings they point to.
+    lookup: HashTable<I>,
+    hasher: S,
+}
+
+impl DupStrIndexer {
+    /// Create a new instance of `DupStrIndexer`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Constructs a new, empty `DupStrIndexer` with at least the specified capacity
+    /// for the number of strings, and for the total length of all strings in bytes.
+    #[must_use]
+    pub fn with_capacity(capacity: usize, bytes_capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, bytes_capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<I: IndexType> Default for DupStrIndexer<DefaultHashBuilder, I> {
+    fn default() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+}
+
+impl<S: BuildHasher, I: IndexType> DupStrIndexer<S, I> {
+    /// Create a new instance of `DupStrIndexer` using the provided hasher.
+    #[must_use]
+    pub fn with_hasher(hasher: S) -> Self {
+        Self {
+            arena: Arena::with_capacity(0, 0),
+            lookup: HashTable::new(),
+            hasher,
+        }
+    }
+
+    /// Constructs a new, empty `DupStrIndexer` with at least the specified capacity
+    /// for the number of strings, and for the total length of all strings in bytes,
+    /// using the provided hasher.
+    #[must_use]
+    pub fn with_capacity_and_hasher(capacity: usize, bytes_capacity: usize, hasher: S) -> Self {
+        Self {
+            arena: Arena::with_capacity(capacity, bytes_capacity),
+            lookup: HashTable::with_capacity(capacity),
+            hasher,
+        }
+    }
+
+    /// Get the number of strings in the indexer.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.arena.offsets.len() - 1
+    }
+
+    /// Return true if the indexer is empty.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the total length of all strings in bytes.
+    #[inline]
+    #[must_use]
+    pub fn bytes_len(&self) -> usize {
+        self.arena.bytes_len()
+    }
+
+    /// Returns the string at the given index, or `None` if it is out of bounds.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, index: I) -> Option<&str> {
+        let index = index.index();
+        if index < self.len() {
+            Some(self.arena.get(index))
+        } else {
+            None
+        }
+    }
+
+    /// Returns an iterator over all strings in the order of their indexes.
+    pub fn iter(&self) -> impl Iterator<Item = &str> + '_ {
+        (0..self.len()).map(|index| self.arena.get(index))
+    }
+
+    /// Returns the start offset of each string in the string table created by [`DupStrIndexer::into_parts`],
+    /// followed by the total length of all strings, so that the string with index `i`
+    /// is located at `offsets[i]..offsets[i + 1]`.
+    #[inline]
+    #[must_use]
+    pub fn offsets(&self) -> &[usize] {
+        &self.arena.offsets
+    }
+
+    /// Converts the indexer into a string table: the buffer with all strings concatenated
+    /// in the order of their indexes, and the start offset of each string in the buffer,
+    /// followed by the length of the buffer.
+    /// If the strings are stored in more than one chunk, they are copied into a new buffer once.
+    #[must_use]
+    pub fn into_parts(mut self) -> (String, Vec<usize>) {
+        let buffer = if self.arena.chunks.len() == 1 {
+            self.arena.chunks.swap_remove(0)
+        } else {
+            let mut buffer = String::with_capacity(self.arena.bytes_len());
+            for index in 0..self.len() {
+                buffer.push_str(self.arena.get(index));
+            }
+            buffer
+        };
+        (buffer, self.arena.offsets)
+    }
+
+    /// Insert a string into the indexer if it doesn't already exist,
+    /// and return the index of the string.
+    ///
+    /// # Panics
+    /// Panics if the number of strings exceeds the capacity of the index type `I`.
+    pub fn insert_ref(&mut self, value: &str) -> I {
+        self.insert_ref_full(value).0
+    }
+
+    /// Insert a string into the indexer if it doesn't already exist,
+    /// and return the index of the string, and `true` if the string was newly inserted.
+    ///
+    /// # Panics
+    /// Panics if the number of strings exceeds the capacity of the index type `I`.
+    pub fn insert_ref_full(&mut self, value: &str) -> (I, bool) {
+        let hash = make_hash(&self.hasher, value);
+        let arena = &self.arena;
+        let hasher = &self.hasher;
+        match self.lookup.entry(
+            hash,
+            |&i| arena.get(i.index()) == value,
+            |&i| make_hash(hasher, arena.get(i.index())),
+        ) {
+            Occupied(entry) => (*entry.get(), false),
+            Vacant(entry) => {
+                let index = next_index(self.arena.offsets.len() - 1);
+                entry.insert(index);
+                self.arena.push(value);
+                (index, true)
+            }
+        }
+    }
+
+    /// Return the index of the string if it exists in the indexer, without inserting it.
+    #[must_use]
+    pub fn get_index_of(&self, value: &str) -> Option<I> {
+        let hash = make_hash(&self.hasher, value);
+        self.lookup
+            .find(hash, |&i| self.arena.get(i.index()) == value)
+            .copied()
+    }
+
+    /// Return true if the string exists in the indexer.
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, value: &str) -> bool {
+        self.get_index_of(value).is_some()
+    }
+}
+
+/// The strings of the indexer, appended to chunks that are never reallocated once full.
+struct Arena {
+    chunks: Vec<String>,
+    /// The chunk that strings are currently appended to, if any.
+    current: Option<usize>,
+    /// The chunk of each string.
+    string_chunks: Vec<u32>,
+    /// The start offset of each string in its chunk.
+    starts: Vec<usize>,
+    /// The start offset of each string as if all strings were concatenated in the order of their indexes,
+    /// followed by the total length of all strings.
+    offsets: Vec<usize>,
+}
+
+impl Arena {
+    fn with_capacity(capacity: usize, bytes_capacity: usize) -> Self {
+        let mut offsets = Vec::with_capacity(capacity + 1);
+        offsets.push(0);
+        let (chunks, current) = if bytes_capacity == 0 {
+            (Vec::new(), None)
+        } else {
+            (vec![String::with_capacity(bytes_capacity)], Some(0))
+        };
+        Self {
+            chunks,
+            current,
+            string_chunks: Vec::with_capacity(capacity),
+            starts: Vec::with_capacity(capacity),
+            offsets,
+        }
+    }
+
+    #[inline]
+    fn bytes_len(&self) -> usize {
+        self.offsets[self.offsets.len() - 1]
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> &str {
+        let start = self.starts[index];
+        let len = self.offsets[index + 1] - self.offsets[index];
+        &self.chunks[self.string_chunks[index] as usize][start..start + len]
+    }
+
+    fn push(&mut self, value: &str) {
+        let chunk = match self.current {
+            Some(current)
+                if self.chunks[current].capacity() - self.chunks[current].len() >= value.len() =>
+            {
+                current
+            }
+            _ => {
+                // Strings that fill a whole chunk get their own one, so the current chunk is still filled
+                self.chunks
+                    .push(String::with_capacity(value.len().max(CHUNK_SIZE)));
+                if value.len() < CHUNK_SIZE {
+                    self.current = Some(self.chunks.len() - 1);
+                }
+                self.chunks.len() - 1
+            }
+        };
+        let Ok(chunk_index) = u32::try_from(chunk) else {
+            panic!("too many string chunks");
+        };
+        self.starts.push(self.chunks[chunk].len());
+        self.chunks[chunk].push_str(value);
+        self.string_chunks.push(chunk_index);
+        self.offsets.push(self.bytes_len() + value.len());
+    }
+}
+
+impl<S, I: IndexType> Index<I> for DupStrIndexer<S, I> {
+    type Output = str;
+
+    #[inline]
+    fn index(&self, index: I) -> &Self::Output {
+        let index = index.index();
+        assert!(
+            index < self.arena.string_chunks.len(),
+            "index out of bounds"
+        );
+        self.arena.get(index)
+    }
+}
+
+impl<S, I> Debug for DupStrIndexer<S, I> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_map()
+            .entries(
+                (0..self.arena.string_chunks.len()).map(|index| (index, self.arena.get(index))),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::RandomState;
+
+    use super::*;
+
+    #[test]
+    fn test_str_indexer() {
+        let mut di = DupStrIndexer::with_capacity(5, 10);
+        assert!(di.is_empty());
+        assert_eq!(di.insert_ref("foo"), 0);
+        assert_eq!(di.insert_ref("bar"), 1);
+        assert_eq!(di.insert_ref("foo"), 0);
+        assert_eq!(di.insert_ref_full(""), (2, true));
+        assert_eq!(di.insert_ref_full("bar"), (1, false));
+        assert_eq!(di.insert_ref("ключ"), 3);
+        assert_eq!(&di[1], "bar");
+        assert_eq!(&di[2], "");
+        assert_eq!(di.get(3), Some("ключ"));
+        assert_eq!(di.get(4), None);
+        assert_eq!(di.get_index_of("ключ"), Some(3));
+        assert!(di.contains(""));
+        assert!(!di.contains("baz"));
+        assert_eq!(di.len(), 4);
+        assert_eq!(di.bytes_len(), 14);
+        assert_eq!(
+            di.iter().collect::<Vec<_>>(),
+            vec!["foo", "bar", "", "ключ"]
+        );
+        assert_eq!(
+            format!("{di:?}"),
+            r#"{0: "foo", 1: "bar", 2: "", 3: "ключ"}"#
+        );
+        assert_eq!(di.offsets(), &[0, 3, 6, 6, 14]);
+        let (bytes, offsets) = di.into_parts();
+        assert_eq!(bytes, "foobarключ");
+        assert_eq!(offsets, vec![0, 3, 6, 6, 14]);
+    }
+
+    #[test]
+    fn test_custom_hasher_and_index() {
+        let mut di: DupStrIndexer<RandomState, u16> =
+            DupStrIndexer::with_hasher(RandomState::new());
+        for value in 0..100 {
+            assert_eq!(di.insert_ref(&value.to_string()), value);
+        }
+        for value in 0..100 {
+            assert_eq!(di.insert_ref(&value.to_string()), value);
+            assert_eq!(&di[value], value.to_string());
+        }
+        assert_eq!(di.len(), 100);
+    }
+
+    #[test]
+    fn test_many_chunks() {
+        let mut di = DupStrIndexer::new();
+        let long = "x".repeat(CHUNK_SIZE + 1);
+        let halves: Vec<String> = ["a", "b", "c"]
+            .iter()
+            .map(|v| v.repeat(CHUNK_SIZE / 2))
+            .collect();
+        assert_eq!(di.insert_ref("foo"), 0);
+        assert_eq!(di.insert_ref(&long), 1);
+        for (index, value) in halves.iter().enumerate() {
+            assert_eq!(di.insert_ref(value), index + 2);
+        }
+        // The long string got its own chunk, while "foo" and the first half still share the first one
+        assert_eq!(di.arena.chunks.len(), 3);
+        assert_eq!(di.arena.string_chunks, vec![0, 1, 0, 2, 2]);
+        assert_eq!(di.insert_ref(&long), 1);
+        assert_eq!(di.insert_ref(&halves[1]), 3);
+        assert_eq!(di.get_index_of(&halves[2]), Some(4));
+        assert_eq!(&di[1], long);
+        assert_eq!(di.iter().last(), Some(halves[2].as_str()));
+
+        let (bytes, offsets) = di.into_parts();
+        assert_eq!(offsets.len(), 6);
+        assert_eq!(offsets[5], bytes.len());
+        assert_eq!(&bytes[offsets[0]..offsets[1]], "foo");
+        assert_eq!(&bytes[offsets[1]..offsets[2]], long);
+        assert_eq!(&bytes[offsets[4]..offsets[5]], halves[2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn test_index_out_of_bounds() {
+        let di = DupStrIndexer::new();
+        let _ = &di[0];
+    }
+}